@@ -1,3 +1,4 @@
+use crate::shaders::{shader_file, ComputePipeline};
 use egui_wgpu::wgpu;
 
 #[repr(C)]
@@ -10,6 +11,16 @@ struct RawUniformData {
     _pad: u32,
 }
 
+// the color pickers give linear colors, the scene is stored srgb encoded
+// the same way as the pngs it's loaded from
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1. / 2.4) - 0.055
+    }
+}
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum BrushShape {
     Square = 0,
@@ -23,7 +34,7 @@ pub struct Brush {
 
     out_texture_bgl: wgpu::BindGroupLayout,
 
-    pipeline: wgpu::ComputePipeline,
+    pipeline: ComputePipeline,
 }
 
 impl Brush {
@@ -35,8 +46,6 @@ impl Brush {
             mapped_at_creation: false,
         });
 
-        let shader_module = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
-
         let uniform_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("brush uniform bind group layout"),
             entries: &[wgpu::BindGroupLayoutEntry {
@@ -80,13 +89,12 @@ impl Brush {
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("brush pipeline"),
-            layout: Some(&pipeline_layout),
-            module: &shader_module,
-            entry_point: "main",
-            compilation_options: Default::default(),
-        });
+        let pipeline = ComputePipeline::new(
+            device,
+            "brush pipeline",
+            shader_file!("src/brush/shader.wgsl"),
+            pipeline_layout,
+        );
 
         Self {
             uniform_buffer,
@@ -98,6 +106,11 @@ impl Brush {
         }
     }
 
+    pub fn reload_shaders(&mut self, device: &wgpu::Device) -> Result<(), String> {
+        self.pipeline.reload(device)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn draw(
         &self,
         device: &wgpu::Device,
@@ -109,7 +122,7 @@ impl Brush {
         color: [f32; 3],
     ) {
         let uniform_data = RawUniformData {
-            color,
+            color: color.map(linear_to_srgb),
             shape: shape as u32,
             pos,
             radius: size as f32 / 2.,
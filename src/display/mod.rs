@@ -0,0 +1,286 @@
+use crate::screenpass::{self, ScreenPass};
+use crate::viewport::Viewport;
+use egui_wgpu::wgpu;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Tonemap {
+    Clamp = 0,
+    Reinhard = 1,
+    Aces = 2,
+    AgX = 3,
+}
+
+impl std::fmt::Display for Tonemap {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Tonemap::Clamp => "Clamp",
+                Tonemap::Reinhard => "Reinhard",
+                Tonemap::Aces => "ACES",
+                Tonemap::AgX => "AgX",
+            }
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DisplayConfig {
+    /// in stops
+    pub exposure: f32,
+    pub tonemap: Tonemap,
+    /// shows luminance in stops instead of the tonemapped image
+    pub false_color: bool,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        DisplayConfig {
+            exposure: 0.,
+            tonemap: Tonemap::Clamp,
+            false_color: false,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct RawUniformData {
+    exposure_mult: f32,
+    tonemap: u32,
+    false_color: u32,
+    encode_srgb: u32,
+    scene_rect: [f32; 4],
+    nearest: u32,
+    _pad: [u32; 3],
+}
+
+/// turns the linear radiance into something that can be shown on the surface
+///
+/// exposure, then tonemapping or false color, then the encode the surface format needs
+pub struct Display {
+    pub config: DisplayConfig,
+
+    out_format: wgpu::TextureFormat,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    screenpass: ScreenPass,
+    // renders into 8 bit images for saving
+    image_screenpass: ScreenPass,
+}
+
+impl Display {
+    const IMAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+    fn create_screenpass(device: &wgpu::Device, out_format: wgpu::TextureFormat) -> ScreenPass {
+        let bind_group_layout_binding_types: &[_] = &[
+            wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+        ];
+        let shader_module = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+
+        ScreenPass::new(
+            device,
+            Some("display"),
+            bind_group_layout_binding_types,
+            shader_module,
+            &[Some(wgpu::ColorTargetState {
+                format: out_format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        )
+    }
+
+    pub fn new(device: &wgpu::Device, out_format: wgpu::TextureFormat) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("display sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("display uniform buffer"),
+            size: std::mem::size_of::<RawUniformData>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Display {
+            config: DisplayConfig::default(),
+
+            out_format,
+            sampler,
+            uniform_buffer,
+            screenpass: Display::create_screenpass(device, out_format),
+            image_screenpass: Display::create_screenpass(device, Display::IMAGE_FORMAT),
+        }
+    }
+
+    // window pixels per scene pixel from which pixels are shown as squares
+    const NEAREST_SCALE: f32 = 2.;
+
+    fn write_uniforms(
+        &self,
+        queue: &wgpu::Queue,
+        out_format: wgpu::TextureFormat,
+        scene_rect: [f32; 4],
+        nearest: bool,
+    ) {
+        let uniform_data = RawUniformData {
+            exposure_mult: f32::exp2(self.config.exposure),
+            tonemap: self.config.tonemap as u32,
+            false_color: self.config.false_color as u32,
+            // srgb targets encode on write
+            encode_srgb: !out_format.is_srgb() as u32,
+            scene_rect,
+            nearest: nearest as u32,
+            _pad: [0; 3],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform_data));
+    }
+
+    fn draw(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        screenpass: &ScreenPass,
+        in_texture: &wgpu::Texture,
+        out_texture: &wgpu::Texture,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        screenpass.render(&screenpass::ScreenPassRenderDescriptor {
+            device,
+            queue,
+            bind_group_resources: &[
+                wgpu::BindingResource::TextureView(
+                    &in_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                ),
+                wgpu::BindingResource::Sampler(&self.sampler),
+                self.uniform_buffer.as_entire_binding(),
+            ],
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &out_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                ops: wgpu::Operations::default(),
+                resolve_target: None,
+            })],
+            viewport: None,
+            timestamp_writes,
+        });
+    }
+
+    /// draws in_texture where the viewport puts the scene and clears the rest
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        in_texture: &wgpu::Texture,
+        out_texture: &wgpu::Texture,
+        viewport: &Viewport,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        self.write_uniforms(
+            queue,
+            self.out_format,
+            viewport.scene_rect(),
+            viewport.scale() >= Display::NEAREST_SCALE,
+        );
+        self.draw(
+            device,
+            queue,
+            &self.screenpass,
+            in_texture,
+            out_texture,
+            timestamp_writes,
+        );
+    }
+
+    /// in_texture the way it's shown in the window, as an Rgba8Unorm texture of the same size
+    pub fn render_image(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        in_texture: &wgpu::Texture,
+    ) -> wgpu::Texture {
+        let image = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("display image"),
+            size: in_texture.size(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Display::IMAGE_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let size = (in_texture.width() as f32, in_texture.height() as f32);
+        self.write_uniforms(queue, Display::IMAGE_FORMAT, [0., 0., size.0, size.1], true);
+        self.draw(
+            device,
+            queue,
+            &self.image_screenpass,
+            in_texture,
+            &image,
+            None,
+        );
+        image
+    }
+
+    pub fn render_egui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Display")
+            .default_size(egui::Vec2::new(1., 1.))
+            .show(ctx, |ui| {
+                ui.heading("Exposure");
+                ui.add(egui::Slider::new(&mut self.config.exposure, -10.0..=10.).suffix(" EV"));
+
+                ui.heading("Tonemapping");
+                ui.add_enabled_ui(!self.config.false_color, |ui| {
+                    egui::ComboBox::from_id_source("display tonemap")
+                        .selected_text(format!("{}", self.config.tonemap))
+                        .show_ui(ui, |ui| {
+                            for tonemap in [
+                                Tonemap::Clamp,
+                                Tonemap::Reinhard,
+                                Tonemap::Aces,
+                                Tonemap::AgX,
+                            ] {
+                                ui.selectable_value(
+                                    &mut self.config.tonemap,
+                                    tonemap,
+                                    format!("{}", tonemap),
+                                );
+                            }
+                        });
+                });
+
+                ui.checkbox(&mut self.config.false_color, "False color")
+                    .on_hover_text(
+                        "luminance in stops around middle gray, black at -6, white at +6",
+                    );
+
+                ui.separator();
+                ui.colored_label(
+                    egui::Color32::from_rgb(150, 150, 150),
+                    format!(
+                        "Output: {:?}, {}",
+                        self.out_format,
+                        match self.out_format.is_srgb() {
+                            true => "encoded by the surface",
+                            false => "encoded in the shader",
+                        }
+                    ),
+                );
+            });
+    }
+}
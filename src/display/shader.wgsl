@@ -0,0 +1,138 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4f,
+    @location(0) tex_coord: vec2f,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vid: u32) -> VertexOutput {
+    var poses = array(
+        vec2f(0., 1.),
+        vec2f(1., 1.),
+        vec2f(0., 0.),
+        vec2f(1., 0.),
+    );
+
+    let pos = vec2f(poses[vid].x * 2. - 1., -poses[vid].y * 2. + 1.);
+    return VertexOutput(vec4f(pos, 0, 1), poses[vid]);
+}
+
+struct uniform_data {
+    // 2^exposure
+    exposure_mult: f32,
+    // 0 - clamp, 1 - reinhard, 2 - aces, 3 - agx
+    tonemap: u32,
+    false_color: u32,
+    // 1 if the target isn't srgb and the encoding has to happen here
+    encode_srgb: u32,
+    // where the scene goes in the target, [x, y, width, height] in pixels
+    scene_rect: vec4f,
+    // 1 when zoomed in far enough to see the pixels
+    nearest: u32,
+}
+
+@group(0) @binding(0)
+var in_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var texture_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> uniforms: uniform_data;
+
+fn luminance(color: vec3f) -> f32 {
+    return dot(color, vec3f(0.2126, 0.7152, 0.0722));
+}
+
+fn reinhard(color: vec3f) -> vec3f {
+    return color / (1. + color);
+}
+
+// krzysztof narkowicz's fit of the aces filmic curve
+fn aces(color: vec3f) -> vec3f {
+    let x = color * 0.6;
+    return saturate((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14));
+}
+
+// the minimal agx from benjamin wrensch, with the polynomial contrast approximation
+fn agx_contrast(x: vec3f) -> vec3f {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+}
+
+fn agx(color: vec3f) -> vec3f {
+    let inset = mat3x3f(
+        vec3f(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+        vec3f(0.0784335999999992, 0.878468636469772, 0.0784336),
+        vec3f(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+    );
+    let outset = mat3x3f(
+        vec3f(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+        vec3f(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+        vec3f(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var x = inset * color;
+    x = clamp(log2(max(x, vec3f(1e-10))), vec3f(min_ev), vec3f(max_ev));
+    x = (x - min_ev) / (max_ev - min_ev);
+    x = agx_contrast(x);
+    x = outset * x;
+    // agx ends in display encoding, the encode below expects linear values
+    return pow(saturate(x), vec3f(2.2));
+}
+
+// luminance in stops around middle gray, from black at -6 to white at +6
+fn false_color(color: vec3f) -> vec3f {
+    var ramp = array(
+        vec3f(0., 0., 0.),
+        vec3f(0., 0., 1.),
+        vec3f(0., 1., 1.),
+        vec3f(0., 1., 0.),
+        vec3f(1., 1., 0.),
+        vec3f(1., 0., 0.),
+        vec3f(1., 1., 1.),
+    );
+    let stops = log2(max(luminance(color), 1e-10) / 0.18);
+    let t = clamp((stops + 6.) / 2., 0., 6.);
+    let i = min(u32(t), 5u);
+    return mix(ramp[i], ramp[i + 1], t - f32(i));
+}
+
+fn linear_to_srgb(color: vec3f) -> vec3f {
+    return select(1.055 * pow(color, vec3f(1. / 2.4)) - 0.055, color * 12.92, color <= vec3f(0.0031308));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    let uv = (in.clip_position.xy - uniforms.scene_rect.xy) / uniforms.scene_rect.zw;
+    if any(uv < vec2f(0.)) || any(uv >= vec2f(1.)) {
+        return vec4f(0., 0., 0., 1.);
+    }
+
+    let dims = textureDimensions(in_texture);
+    var sampled: vec4f;
+    if uniforms.nearest == 1 {
+        sampled = textureLoad(in_texture, vec2u(uv * vec2f(dims)), 0);
+    } else {
+        sampled = textureSampleLevel(in_texture, texture_sampler, uv, 0.);
+    }
+    let radiance = max(sampled.rgb, vec3f(0.));
+    let exposed = radiance * uniforms.exposure_mult;
+
+    var color: vec3f;
+    if uniforms.false_color == 1 {
+        color = false_color(exposed);
+    } else {
+        switch uniforms.tonemap {
+            case 1u: { color = reinhard(exposed); }
+            case 2u: { color = aces(exposed); }
+            case 3u: { color = agx(exposed); }
+            default: { color = saturate(exposed); }
+        }
+    }
+
+    if uniforms.encode_srgb == 1 {
+        color = linear_to_srgb(color);
+    }
+    return vec4f(color, 1.);
+}
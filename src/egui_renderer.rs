@@ -33,6 +33,7 @@ impl EguiRenderer {
         self.state.on_window_event(window, event)
     }
 
+    /// returns how long egui can wait before it wants to be drawn again
     pub fn render(
         &mut self,
         device: &wgpu::Device,
@@ -41,7 +42,7 @@ impl EguiRenderer {
         out_texture_view: &wgpu::TextureView,
         screen_descriptor: egui_wgpu::ScreenDescriptor,
         run_ui: impl FnOnce(&egui::Context),
-    ) {
+    ) -> std::time::Duration {
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("egui renderer encoder"),
         });
@@ -93,5 +94,10 @@ impl EguiRenderer {
         for tex in &full_output.textures_delta.free {
             self.renderer.free_texture(tex);
         }
+
+        full_output
+            .viewport_output
+            .get(&egui::ViewportId::ROOT)
+            .map_or(std::time::Duration::MAX, |v| v.repaint_delay)
     }
 }
@@ -0,0 +1,428 @@
+use crate::shaders::{shader_file, ComputePipeline};
+use egui_wgpu::wgpu;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// error between the two renderers, over the whole image or the selected rect
+#[derive(Clone, Copy)]
+pub struct MetricsResult {
+    pub mse: f32,
+    pub max_error: f32,
+    /// mean ssim of the 8x8 windows, on luminance
+    pub ssim: f32,
+    pub pixels: u32,
+}
+
+impl MetricsResult {
+    pub fn rmse(&self) -> f32 {
+        self.mse.sqrt()
+    }
+
+    /// with a peak of 1, infinite for identical images
+    pub fn psnr(&self) -> f32 {
+        -10. * self.mse.log10()
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct RawUniformData {
+    rect: [u32; 4],
+    groups_x: u32,
+    num_partials: u32,
+    _pad: [u32; 2],
+}
+
+/// computes error metrics between the difference temp textures on the gpu
+///
+/// a tile pass reduces 8x8 windows to partial sums, a single workgroup sums those up
+/// and the 16 byte result is read back, it shows up a frame or two later
+pub struct Metrics {
+    /// [x, y, width, height] in pixels, the whole image if None
+    pub rect: Option<[u32; 4]>,
+    result: Option<MetricsResult>,
+
+    texture_size: (u32, u32),
+    uniform_buffer: wgpu::Buffer,
+    partials_buffer: wgpu::Buffer,
+    result_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    tile_pipeline: ComputePipeline,
+    reduce_pipeline: ComputePipeline,
+
+    mapped: Arc<AtomicBool>,
+    in_flight: Cell<bool>,
+    // the result buffer got a newer result while the readback buffer was mapped
+    pending: Cell<bool>,
+}
+
+impl Metrics {
+    const WINDOW_SIZE: u32 = 8;
+    const RESULT_SIZE: u64 = 16;
+
+    fn num_groups(size: (u32, u32)) -> (u32, u32) {
+        (
+            u32::div_ceil(size.0, Metrics::WINDOW_SIZE),
+            u32::div_ceil(size.1, Metrics::WINDOW_SIZE),
+        )
+    }
+
+    fn create_partials_buffer(device: &wgpu::Device, texture_size: (u32, u32)) -> wgpu::Buffer {
+        let groups = Metrics::num_groups(texture_size);
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("metrics partials buffer"),
+            size: (groups.0 * groups.1).max(1) as u64 * 16,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        partials_buffer: &wgpu::Buffer,
+        result_buffer: &wgpu::Buffer,
+        textures: &[wgpu::Texture; 2],
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("metrics bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &textures[0].create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        &textures[1].create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: partials_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: result_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    pub fn new(device: &wgpu::Device, textures: &[wgpu::Texture; 2]) -> Self {
+        let texture_size = (textures[0].width(), textures[0].height());
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("metrics uniform buffer"),
+            size: std::mem::size_of::<RawUniformData>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let partials_buffer = Metrics::create_partials_buffer(device, texture_size);
+        let result_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("metrics result buffer"),
+            size: Metrics::RESULT_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("metrics readback buffer"),
+            size: Metrics::RESULT_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("metrics bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(1),
+                texture_entry(2),
+                storage_entry(3),
+                storage_entry(4),
+            ],
+        });
+
+        let bind_group = Metrics::create_bind_group(
+            device,
+            &bind_group_layout,
+            &uniform_buffer,
+            &partials_buffer,
+            &result_buffer,
+            textures,
+        );
+
+        let pipeline_layout = || {
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("metrics pipeline layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            })
+        };
+        let tile_pipeline = ComputePipeline::new(
+            device,
+            "metrics tile pipeline",
+            shader_file!("src/gi/difference/metrics.wgsl"),
+            pipeline_layout(),
+        );
+        let reduce_pipeline = ComputePipeline::new(
+            device,
+            "metrics reduce pipeline",
+            shader_file!("src/gi/difference/metrics_reduce.wgsl"),
+            pipeline_layout(),
+        );
+
+        Metrics {
+            rect: None,
+            result: None,
+
+            texture_size,
+            uniform_buffer,
+            partials_buffer,
+            result_buffer,
+            readback_buffer,
+            bind_group_layout,
+            bind_group,
+            tile_pipeline,
+            reduce_pipeline,
+
+            mapped: Arc::new(AtomicBool::new(false)),
+            in_flight: Cell::new(false),
+            pending: Cell::new(false),
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, textures: &[wgpu::Texture; 2]) {
+        self.texture_size = (textures[0].width(), textures[0].height());
+        self.partials_buffer = Metrics::create_partials_buffer(device, self.texture_size);
+        self.bind_group = Metrics::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+            &self.partials_buffer,
+            &self.result_buffer,
+            textures,
+        );
+    }
+
+    pub fn reload_shaders(&mut self, device: &wgpu::Device) -> Result<(), String> {
+        crate::shaders::join_errors([
+            self.tile_pipeline.reload(device),
+            self.reduce_pipeline.reload(device),
+        ])
+    }
+
+    /// whether a result is still on its way, and another frame is needed to show it
+    pub fn waiting(&self) -> bool {
+        self.in_flight.get()
+    }
+
+    /// the measured rect, clamped to the textures
+    pub fn clamped_rect(&self) -> [u32; 4] {
+        let (width, height) = self.texture_size;
+        let [x, y, w, h] = self.rect.unwrap_or([0, 0, width, height]);
+        let x = x.min(width);
+        let y = y.min(height);
+        [x, y, w.min(width - x), h.min(height - y)]
+    }
+
+    fn start_readback(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.copy_buffer_to_buffer(
+            &self.result_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            Metrics::RESULT_SIZE,
+        );
+        self.in_flight.set(true);
+    }
+
+    fn map_readback(&self) {
+        let mapped = self.mapped.clone();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if result.is_ok() {
+                    mapped.store(true, Ordering::Release);
+                }
+            });
+    }
+
+    /// measures the current contents of the temp textures
+    pub fn compute(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        timestamp_writes: Option<wgpu::ComputePassTimestampWrites>,
+    ) {
+        let rect = self.clamped_rect();
+        let groups = Metrics::num_groups((rect[2], rect[3]));
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&RawUniformData {
+                rect,
+                groups_x: groups.0,
+                num_partials: groups.0 * groups.1,
+                _pad: [0; 2],
+            }),
+        );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("metrics encoder"),
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("metrics compute pass"),
+                timestamp_writes,
+            });
+            compute_pass.set_bind_group(0, &self.bind_group, &[]);
+            compute_pass.set_pipeline(&self.tile_pipeline);
+            compute_pass.dispatch_workgroups(groups.0, groups.1, 1);
+            compute_pass.set_pipeline(&self.reduce_pipeline);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+
+        let start = !self.in_flight.get();
+        if start {
+            self.start_readback(&mut encoder);
+        } else {
+            self.pending.set(true);
+        }
+        queue.submit(Some(encoder.finish()));
+        if start {
+            self.map_readback();
+        }
+    }
+
+    /// picks up a finished readback, and starts the next one if a newer result is waiting
+    pub fn poll(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if !self.in_flight.get() {
+            return;
+        }
+        device.poll(wgpu::Maintain::Poll);
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return;
+        }
+
+        {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let [squared_error, max_error, ssim, pixels]: [f32; 4] = core::array::from_fn(|i| {
+                f32::from_le_bytes(data[i * 4..i * 4 + 4].try_into().unwrap())
+            });
+            self.result = (pixels > 0.).then(|| MetricsResult {
+                mse: squared_error / pixels,
+                max_error,
+                ssim: ssim / pixels,
+                pixels: pixels as u32,
+            });
+        }
+        self.readback_buffer.unmap();
+        self.in_flight.set(false);
+
+        if self.pending.take() {
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("metrics readback encoder"),
+            });
+            self.start_readback(&mut encoder);
+            queue.submit(Some(encoder.finish()));
+            self.map_readback();
+        }
+    }
+
+    pub fn render_egui(&mut self, ui: &mut egui::Ui) -> bool {
+        let old_rect = self.rect;
+
+        match self.result {
+            Some(result) => {
+                egui::Grid::new("metrics grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        let mut row = |label: &str, value: String| {
+                            ui.label(label);
+                            ui.monospace(value);
+                            ui.end_row();
+                        };
+                        row("MSE", format!("{:.3e}", result.mse));
+                        row("RMSE", format!("{:.3e}", result.rmse()));
+                        row("PSNR", format!("{:.2} dB", result.psnr()));
+                        row("Max error", format!("{:.3e}", result.max_error));
+                        row("SSIM", format!("{:.5}", result.ssim));
+                        row("Pixels", format!("{}", result.pixels));
+                    });
+            }
+            None => {
+                ui.label("Waiting for the first result");
+            }
+        }
+        ui.colored_label(
+            egui::Color32::from_rgb(150, 150, 150),
+            "PSNR and SSIM assume a peak of 1, SSIM is on luminance",
+        );
+
+        let (width, height) = self.texture_size;
+        let mut restrict = self.rect.is_some();
+        ui.checkbox(&mut restrict, "Restrict to rectangle");
+        match (restrict, self.rect.is_some()) {
+            (true, false) => self.rect = Some([0, 0, width, height]),
+            (false, true) => self.rect = None,
+            _ => {}
+        }
+        if let Some([x, y, w, h]) = &mut self.rect {
+            ui.horizontal(|ui| {
+                ui.label("x");
+                ui.add(egui::DragValue::new(x).range(0..=width.saturating_sub(1)));
+                ui.label("y");
+                ui.add(egui::DragValue::new(y).range(0..=height.saturating_sub(1)));
+            });
+            ui.horizontal(|ui| {
+                ui.label("w");
+                ui.add(egui::DragValue::new(w).range(1..=width));
+                ui.label("h");
+                ui.add(egui::DragValue::new(h).range(1..=height));
+            });
+        }
+
+        self.rect != old_rect
+    }
+}
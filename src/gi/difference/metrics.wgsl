@@ -0,0 +1,83 @@
+// first pass of the error metrics, every workgroup reduces one 8x8 window of the rect
+
+struct uniform_data {
+    // [x, y, width, height] of the measured rect in pixels
+    rect: vec4u,
+    // workgroups along x in the tile pass
+    groups_x: u32,
+    num_partials: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: uniform_data;
+@group(0) @binding(1)
+var temp_texture_1: texture_2d<f32>;
+@group(0) @binding(2)
+var temp_texture_2: texture_2d<f32>;
+// squared error sum, max error, ssim * pixel count, pixel count
+@group(0) @binding(3)
+var<storage, read_write> partials: array<vec4f>;
+
+// sums over the window, see `main`
+var<workgroup> sums: array<array<f32, 8>, 64>;
+
+// ssim constants for a dynamic range of 1
+const C1 = 0.0001;
+const C2 = 0.0009;
+
+fn luminance(color: vec3f) -> f32 {
+    return dot(color, vec3f(0.2126, 0.7152, 0.0722));
+}
+
+@compute
+@workgroup_size(8, 8)
+fn main(@builtin(local_invocation_index) lid: u32, @builtin(workgroup_id) wid: vec3u, @builtin(local_invocation_id) local_id: vec3u) {
+    let offset = wid.xy * 8u + local_id.xy;
+    let pixel_pos = uniforms.rect.xy + offset;
+
+    // squared error, max error, count, a, b, a², b², ab
+    var s = array<f32, 8>(0., 0., 0., 0., 0., 0., 0., 0.);
+    if all(offset < uniforms.rect.zw) {
+        let a = textureLoad(temp_texture_1, pixel_pos, 0).rgb;
+        let b = textureLoad(temp_texture_2, pixel_pos, 0).rgb;
+        let d = a - b;
+        let la = luminance(a);
+        let lb = luminance(b);
+        s = array<f32, 8>(dot(d, d) / 3., max(max(abs(d.r), abs(d.g)), abs(d.b)), 1., la, lb, la * la, lb * lb, la * lb);
+    }
+    sums[lid] = s;
+    workgroupBarrier();
+
+    for (var stride = 32u; stride > 0u; stride /= 2u) {
+        if lid < stride {
+            var mine = sums[lid];
+            var other = sums[lid + stride];
+            for (var i = 0; i < 8; i++) {
+                if i == 1 {
+                    mine[i] = max(mine[i], other[i]);
+                } else {
+                    mine[i] += other[i];
+                }
+            }
+            sums[lid] = mine;
+        }
+        workgroupBarrier();
+    }
+
+    if lid == 0u {
+        let total = sums[0];
+        let n = total[2];
+        var ssim_weighted = 0.;
+        if n > 0. {
+            let mean_a = total[3] / n;
+            let mean_b = total[4] / n;
+            let var_a = max(total[5] / n - mean_a * mean_a, 0.);
+            let var_b = max(total[6] / n - mean_b * mean_b, 0.);
+            let cov = total[7] / n - mean_a * mean_b;
+            let ssim = ((2. * mean_a * mean_b + C1) * (2. * cov + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            ssim_weighted = ssim * n;
+        }
+        partials[wid.y * uniforms.groups_x + wid.x] = vec4f(total[0], total[1], ssim_weighted, n);
+    }
+}
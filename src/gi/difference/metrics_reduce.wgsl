@@ -0,0 +1,43 @@
+// second pass of the error metrics, a single workgroup sums up the partials of the tile pass
+
+struct uniform_data {
+    rect: vec4u,
+    groups_x: u32,
+    num_partials: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: uniform_data;
+@group(0) @binding(3)
+var<storage, read_write> partials: array<vec4f>;
+// same layout as a partial, read back by the cpu
+@group(0) @binding(4)
+var<storage, read_write> result: vec4f;
+
+var<workgroup> sums: array<vec4f, 256>;
+
+fn combine(a: vec4f, b: vec4f) -> vec4f {
+    return vec4f(a.x + b.x, max(a.y, b.y), a.z + b.z, a.w + b.w);
+}
+
+@compute
+@workgroup_size(256)
+fn main(@builtin(local_invocation_index) lid: u32) {
+    var total = vec4f(0.);
+    for (var i = lid; i < uniforms.num_partials; i += 256u) {
+        total = combine(total, partials[i]);
+    }
+    sums[lid] = total;
+    workgroupBarrier();
+
+    for (var stride = 128u; stride > 0u; stride /= 2u) {
+        if lid < stride {
+            sums[lid] = combine(sums[lid], sums[lid + stride]);
+        }
+        workgroupBarrier();
+    }
+
+    if lid == 0u {
+        result = sums[0];
+    }
+}
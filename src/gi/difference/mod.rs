@@ -1,7 +1,12 @@
+mod metrics;
+
+use crate::profiler::GpuProfiler;
+use crate::shaders::{self, shader_file, ComputePipeline};
 use egui_wgpu::wgpu;
+pub use metrics::Metrics;
 
 #[repr(C)]
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum DiffMode {
     Abs = 0,
     FirstMinusSecond = 1,
@@ -10,7 +15,7 @@ pub enum DiffMode {
     Second = 4,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct DiffConfig {
     pub mode: DiffMode,
     pub mult: f32,
@@ -27,11 +32,13 @@ pub struct Difference {
     pub config: DiffConfig,
 
     temp_textures: [wgpu::Texture; 2],
+    pub metrics: Metrics,
 
     uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
     bind_group: wgpu::BindGroup,
     out_bind_group_layout: wgpu::BindGroupLayout,
-    pipeline: wgpu::ComputePipeline,
+    pipeline: ComputePipeline,
 }
 
 impl Difference {
@@ -58,9 +65,39 @@ impl Difference {
         [ct(), ct()]
     }
 
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        temp_textures: &[wgpu::Texture; 2],
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("diff bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &temp_textures[0].create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        &temp_textures[1].create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+            ],
+        })
+    }
+
     pub fn new(device: &wgpu::Device, texture_size: (u32, u32)) -> Self {
         let temp_textures = Difference::create_temp_textures(device, texture_size);
-        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+        let metrics = Metrics::new(device, &temp_textures);
 
         let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("diff uniform buffer"),
@@ -105,28 +142,12 @@ impl Difference {
             ],
         });
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("diff bind group"),
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: uniform_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::TextureView(
-                        &temp_textures[0].create_view(&wgpu::TextureViewDescriptor::default()),
-                    ),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::TextureView(
-                        &temp_textures[1].create_view(&wgpu::TextureViewDescriptor::default()),
-                    ),
-                },
-            ],
-        });
+        let bind_group = Difference::create_bind_group(
+            device,
+            &bind_group_layout,
+            &uniform_buffer,
+            &temp_textures,
+        );
 
         let out_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -149,13 +170,12 @@ impl Difference {
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("diff compute pipeline"),
-            layout: Some(&pipeline_layout),
-            module: &shader,
-            entry_point: "main",
-            compilation_options: Default::default(),
-        });
+        let pipeline = ComputePipeline::new(
+            device,
+            "diff compute pipeline",
+            shader_file!("src/gi/difference/shader.wgsl"),
+            pipeline_layout,
+        );
 
         Difference {
             config: DiffConfig {
@@ -163,8 +183,10 @@ impl Difference {
                 mult: 1.,
             },
             temp_textures,
+            metrics,
 
             uniform_buffer,
+            bind_group_layout,
             bind_group,
             out_bind_group_layout,
 
@@ -172,7 +194,31 @@ impl Difference {
         }
     }
 
-    pub fn render(&self, device: &wgpu::Device, queue: &wgpu::Queue, out_texture: &wgpu::Texture) {
+    pub fn resize(&mut self, device: &wgpu::Device, texture_size: (u32, u32)) {
+        self.temp_textures = Difference::create_temp_textures(device, texture_size);
+        self.bind_group = Difference::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+            &self.temp_textures,
+        );
+        self.metrics.resize(device, &self.temp_textures);
+    }
+
+    pub fn reload_shaders(&mut self, device: &wgpu::Device) -> Result<(), String> {
+        shaders::join_errors([
+            self.pipeline.reload(device),
+            self.metrics.reload_shaders(device),
+        ])
+    }
+
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        out_texture: &wgpu::Texture,
+        profiler: &GpuProfiler,
+    ) {
         let out_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("a diff out bind group"),
             layout: &self.out_bind_group_layout,
@@ -189,7 +235,7 @@ impl Difference {
             0,
             bytemuck::bytes_of(&RawUniformData {
                 mode: self.config.mode as u32,
-                mult: self.config.mult.into(),
+                mult: self.config.mult,
             }),
         );
 
@@ -198,7 +244,7 @@ impl Difference {
         {
             let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
                 label: Some("diff compute pass"),
-                timestamp_writes: None,
+                timestamp_writes: profiler.compute_pass_timestamps("difference"),
             });
             compute_pass.set_pipeline(&self.pipeline);
             compute_pass.set_bind_group(0, &self.bind_group, &[]);
@@ -211,6 +257,9 @@ impl Difference {
         }
 
         queue.submit(Some(encoder.finish()));
+
+        self.metrics
+            .compute(device, queue, profiler.compute_pass_timestamps("metrics"));
     }
 
     pub fn textures(&self) -> &[wgpu::Texture] {
@@ -0,0 +1,118 @@
+use crate::jfa::JFA;
+use crate::profiler::GpuProfiler;
+use crate::scene::DirtyRect;
+use egui_wgpu::wgpu;
+use std::cell::Cell;
+
+/// the distance field of the scene, shared by all renderers
+///
+/// only recomputed after the scene changes, and only around the change when that's small
+pub struct DistanceField {
+    jfa: JFA,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    texture_size: (u32, u32),
+
+    // what changed in the scene since the last update, taken in `update`
+    stale: Cell<Option<DirtyRect>>,
+}
+
+impl DistanceField {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+
+    fn create_texture(device: &wgpu::Device, size: (u32, u32)) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("sdf texture"),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DistanceField::FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    pub fn new(device: &wgpu::Device, texture_size: (u32, u32)) -> Self {
+        let texture = DistanceField::create_texture(device, texture_size);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        DistanceField {
+            jfa: JFA::new(device, texture_size),
+            texture,
+            view,
+            texture_size,
+
+            stale: Cell::new(Some(DirtyRect::ALL)),
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, new_size: (u32, u32)) {
+        if new_size == self.texture_size {
+            return;
+        }
+        self.texture_size = new_size;
+        self.texture = DistanceField::create_texture(device, new_size);
+        self.view = self
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.jfa.resize(device, new_size);
+        self.stale.set(Some(DirtyRect::ALL));
+    }
+
+    /// marks a part of the scene as changed, it's recomputed on the next update
+    pub fn invalidate(&self, rect: DirtyRect) {
+        let stale = self.stale.get().map_or(rect, |stale| stale.union(rect));
+        self.stale.set(Some(stale));
+    }
+
+    /// recomputes whatever the scene changes since the last update touched
+    pub fn update(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene_texture: &wgpu::Texture,
+        profiler: &GpuProfiler,
+    ) {
+        let Some(stale) = self.stale.take() else {
+            return;
+        };
+        let dirty = (!stale.covers(self.texture_size)).then_some(stale);
+
+        self.jfa.render(
+            device,
+            queue,
+            &scene_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            &self.view,
+            self.texture_size,
+            dirty,
+            profiler,
+        );
+    }
+
+    /// R32Float, the distance to the closest non transparent scene pixel
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// see `JFA::seed_texture`
+    pub fn seed_texture(&self) -> &wgpu::Texture {
+        self.jfa.seed_texture()
+    }
+
+    pub fn reload_shaders(&mut self, device: &wgpu::Device) -> Result<(), String> {
+        // the new shaders might compute something else
+        self.stale.set(Some(DirtyRect::ALL));
+        self.jfa.reload_shaders(device)
+    }
+}
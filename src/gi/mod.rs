@@ -1,35 +1,117 @@
 mod difference;
-mod radiance_cascades;
-mod raymarcher;
+pub mod distance_field;
+pub mod path_tracer;
+pub mod radiance_cascades;
+pub mod raymarcher;
 mod texture;
 
-use difference::Difference;
+use crate::profiler::GpuProfiler;
+use crate::scene::DirtyRect;
+use crate::shaders;
+use crate::viewport::Viewport;
+use difference::{DiffConfig, Difference};
+use distance_field::DistanceField;
 use egui_wgpu::wgpu;
-use radiance_cascades::RadianceCascades;
-use raymarcher::Raymarcher;
+use path_tracer::{PathTracer, PathTracerConfig};
+use radiance_cascades::{RCConfig, RadianceCascades};
+use raymarcher::{Raymarcher, RaymarcherConfig};
 use texture::TextureRenderer;
 
-trait GIRenderer {
+/// wgsl prepended to the shaders that read the scene, for decoding its colors
+pub const SRGB_WGSL: &str = include_str!("srgb.wgsl");
+
+pub trait GIRenderer {
+    /// `sdf` is up to date with `in_texture`
     fn render(
         &self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         in_texture: &wgpu::Texture,
+        sdf: &DistanceField,
         out_texture: &wgpu::Texture,
+        profiler: &GpuProfiler,
     );
 
+    /// returns whether the output changed and has to be rendered again
+    #[allow(unused_variables)]
+    fn render_egui(
+        &mut self,
+        ctx: &egui::Context,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> bool {
+        false
+    }
+
+    /// draws over the scene, `viewport` maps scene pixels to the window
     #[allow(unused_variables)]
-    fn render_egui(&mut self, ctx: &egui::Context, device: &wgpu::Device, queue: &wgpu::Queue) {}
+    fn paint_overlay(&self, painter: &egui::Painter, viewport: &Viewport) {}
+
+    /// ctrl+click at `pos` in scene pixels, returns whether the output has to be rendered again
+    #[allow(unused_variables)]
+    fn pick(&mut self, pos: (f32, f32)) -> bool {
+        false
+    }
 
     #[allow(unused_variables)]
     fn resize(&mut self, device: &wgpu::Device, new_size: (u32, u32)) {}
 
+    /// drops whatever was accumulated over earlier frames, called when the scene or settings change
+    fn reset(&mut self) {}
+
+    /// whether rendering again would improve the output, keeps frames coming while idle
+    fn converging(&self) -> bool {
+        false
+    }
+
+    /// whether something is still being read back from the gpu, keeps frames coming while idle
+    fn waiting(&self) -> bool {
+        false
+    }
+
+    /// rebuilds the pipelines from the shader files on disk, see `shaders::ComputePipeline::reload`
+    #[allow(unused_variables)]
+    fn reload_shaders(&mut self, device: &wgpu::Device) -> Result<(), String> {
+        Ok(())
+    }
+
     fn label(&self) -> String {
         "NO LABEL".to_string()
     }
+
+    /// everything needed to recreate this renderer from a session file
+    fn session(&self) -> RendererSession;
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub enum RendererSession {
+    RadianceCascades {
+        label: String,
+        config: RCConfig,
+    },
+    Raymarcher {
+        label: String,
+        config: RaymarcherConfig,
+    },
+    Texture {
+        label: String,
+        image_path: Option<String>,
+    },
+    PathTracer {
+        label: String,
+        config: PathTracerConfig,
+    },
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct GISession {
+    renderers: Vec<RendererSession>,
+    cur_renderer: CurRenderer,
+    diff_indices: (usize, usize),
+    diff_config: DiffConfig,
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy, serde::Serialize, serde::Deserialize)]
 enum CurRenderer {
     Diff,
     Index(usize),
@@ -43,42 +125,212 @@ pub struct GI {
     difference: Difference,
     diff_indices: (usize, usize),
 
-    cur_window_size: (u32, u32),
+    // computed once for all renderers
+    distance_field: DistanceField,
+
+    cur_texture_size: (u32, u32),
 }
 
 impl GI {
-    pub fn new(device: &wgpu::Device, window_size: (u32, u32)) -> Self {
-        let default_renderer = RadianceCascades::new(&device, window_size, "RC 0".to_owned());
+    pub fn new(device: &wgpu::Device, texture_size: (u32, u32)) -> Self {
+        let default_renderer = RadianceCascades::new(device, texture_size, "RC 0".to_owned());
         GI {
             renderers: vec![Box::new(default_renderer)],
             cur_renderer: CurRenderer::Index(0),
 
-            difference: Difference::new(device, window_size),
+            difference: Difference::new(device, texture_size),
             diff_indices: (0, 0),
 
-            cur_window_size: window_size,
+            distance_field: DistanceField::new(device, texture_size),
+
+            cur_texture_size: texture_size,
         }
     }
 
     pub fn resize(&mut self, device: &wgpu::Device, new_size: (u32, u32)) {
-        self.cur_window_size = new_size;
-        {
-            let old_config = self.difference.config;
-            self.difference = Difference::new(device, new_size);
-            self.difference.config = old_config;
+        if new_size == self.cur_texture_size {
+            return;
         }
+        self.cur_texture_size = new_size;
+        self.difference.resize(device, new_size);
+        self.distance_field.resize(device, new_size);
         for i in 0..self.renderers.len() {
             self.renderers[i].resize(device, new_size);
         }
     }
 
+    fn shown_indices(&self) -> impl Iterator<Item = usize> {
+        let indices = match self.cur_renderer {
+            CurRenderer::Index(i) => [Some(i), None],
+            CurRenderer::Diff => [Some(self.diff_indices.0), Some(self.diff_indices.1)],
+        };
+        indices.into_iter().flatten()
+    }
+
+    /// the renderers that make up the output
+    fn shown_renderers(&self) -> impl Iterator<Item = &dyn GIRenderer> {
+        self.shown_indices()
+            .filter_map(|i| self.renderers.get(i))
+            .map(|renderer| renderer.as_ref())
+    }
+
+    /// returns whether the output has to be rendered again
+    pub fn pick(&mut self, pos: (f32, f32)) -> bool {
+        let mut indices: Vec<usize> = self.shown_indices().collect();
+        // a renderer compared to itself
+        indices.dedup();
+        let mut changed = false;
+        for i in indices {
+            if let Some(renderer) = self.renderers.get_mut(i) {
+                changed |= renderer.pick(pos);
+            }
+        }
+        changed
+    }
+
+    /// a part of the scene was painted over, the distance field is updated on the next render
+    pub fn scene_changed(&mut self, rect: DirtyRect) {
+        self.distance_field.invalidate(rect);
+    }
+
+    pub fn reset(&mut self) {
+        for renderer in &mut self.renderers {
+            renderer.reset();
+        }
+    }
+
+    /// whether a shown renderer still improves with more frames
+    pub fn converging(&self) -> bool {
+        self.shown_renderers().any(|renderer| renderer.converging())
+    }
+
+    pub fn paint_overlay(&self, ctx: &egui::Context, viewport: &Viewport) {
+        // below the windows
+        let painter = ctx.layer_painter(egui::LayerId::background());
+        for renderer in self.shown_renderers() {
+            renderer.paint_overlay(&painter, viewport);
+        }
+    }
+
+    /// the R32Float distance field of the last render
+    pub fn sdf_texture(&self) -> &wgpu::Texture {
+        self.distance_field.texture()
+    }
+
+    /// whether the difference metrics or a shown renderer are still being read back
+    pub fn waiting(&self) -> bool {
+        self.difference.metrics.waiting() || self.shown_renderers().any(|r| r.waiting())
+    }
+
+    pub fn reload_shaders(&mut self, device: &wgpu::Device) -> Result<(), String> {
+        shaders::join_errors(
+            [
+                self.difference.reload_shaders(device),
+                self.distance_field.reload_shaders(device),
+            ]
+            .into_iter()
+            .chain(
+                self.renderers
+                    .iter_mut()
+                    .map(|renderer| renderer.reload_shaders(device)),
+            ),
+        )
+    }
+
+    pub fn session(&self) -> GISession {
+        GISession {
+            renderers: self.renderers.iter().map(|r| r.session()).collect(),
+            cur_renderer: self.cur_renderer,
+            diff_indices: self.diff_indices,
+            diff_config: self.difference.config,
+        }
+    }
+
+    /// everything `load_session` needs from a session for a scene of `texture_size`
+    pub fn check_session(
+        device: &wgpu::Device,
+        session: &GISession,
+        texture_size: (u32, u32),
+    ) -> Result<(), String> {
+        let num_renderers = session.renderers.len();
+        let index_valid = |i: usize| i < num_renderers;
+        if !index_valid(session.diff_indices.0)
+            || !index_valid(session.diff_indices.1)
+            || matches!(session.cur_renderer, CurRenderer::Index(i) if !index_valid(i))
+        {
+            return Err("Invalid renderer index in session".to_owned());
+        }
+
+        for renderer in &session.renderers {
+            if let RendererSession::RadianceCascades { label, config } = renderer {
+                config
+                    .validate()
+                    .map_err(|e| format!("Invalid config of {} in session: {}", label, e))?;
+                if !RadianceCascades::fits(device, texture_size, config) {
+                    return Err(format!(
+                        "The cascades of {} in session are too big for this device",
+                        label
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// replaces the renderers, the session has to pass `check_session` first
+    pub fn load_session(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        session: GISession,
+        texture_size: (u32, u32),
+    ) {
+        self.renderers = session
+            .renderers
+            .into_iter()
+            .map(|r| -> Box<dyn GIRenderer> {
+                match r {
+                    RendererSession::RadianceCascades { label, config } => Box::new(
+                        RadianceCascades::with_config(device, texture_size, label, config),
+                    ),
+                    RendererSession::Raymarcher { label, config } => {
+                        Box::new(Raymarcher::with_config(
+                            device,
+                            texture_size,
+                            wgpu::TextureFormat::Rgba16Float,
+                            label,
+                            config,
+                        ))
+                    }
+                    RendererSession::Texture { label, image_path } => {
+                        let mut renderer = TextureRenderer::new(device, texture_size, label);
+                        if let Some(path) = image_path {
+                            renderer.load_texture_from_file(path, device, queue);
+                        }
+                        Box::new(renderer)
+                    }
+                    RendererSession::PathTracer { label, config } => {
+                        Box::new(PathTracer::with_config(device, texture_size, label, config))
+                    }
+                }
+            })
+            .collect();
+        self.cur_renderer = session.cur_renderer;
+        self.diff_indices = session.diff_indices;
+        self.difference.config = session.diff_config;
+    }
+
     pub fn render(
         &self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         in_texture: &wgpu::Texture,
         out_texture: &wgpu::Texture,
+        profiler: &GpuProfiler,
     ) {
+        self.distance_field
+            .update(device, queue, in_texture, profiler);
+
         match self.cur_renderer {
             CurRenderer::Diff => {
                 if self.diff_indices.0 >= self.renderers.len()
@@ -90,34 +342,56 @@ impl GI {
 
                 let diff_textures = self.difference.textures();
 
-                self.renderers[self.diff_indices.0].render(
-                    device,
-                    queue,
-                    in_texture,
-                    &diff_textures[0],
-                );
-                self.renderers[self.diff_indices.1].render(
+                for (renderer_index, diff_texture) in [self.diff_indices.0, self.diff_indices.1]
+                    .into_iter()
+                    .zip(diff_textures)
+                {
+                    let renderer = &self.renderers[renderer_index];
+                    profiler.set_scope(&renderer.label());
+                    renderer.render(
+                        device,
+                        queue,
+                        in_texture,
+                        &self.distance_field,
+                        diff_texture,
+                        profiler,
+                    );
+                }
+                profiler.set_scope("");
+                self.difference.render(device, queue, out_texture, profiler);
+            }
+            CurRenderer::Index(i) => {
+                profiler.set_scope(&self.renderers[i].label());
+                self.renderers[i].render(
                     device,
                     queue,
                     in_texture,
-                    &diff_textures[1],
+                    &self.distance_field,
+                    out_texture,
+                    profiler,
                 );
-                self.difference.render(device, queue, out_texture);
-            }
-            CurRenderer::Index(i) => {
-                self.renderers[i].render(device, queue, in_texture, out_texture);
+                profiler.set_scope("");
             }
         }
     }
 
-    pub fn render_egui(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, ctx: &egui::Context) {
+    /// returns whether the output changed and has to be rendered again
+    pub fn render_egui(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        ctx: &egui::Context,
+    ) -> bool {
+        self.difference.metrics.poll(device, queue);
+        let old_state = (self.cur_renderer, self.diff_indices, self.difference.config);
+
         egui::Window::new("Renderers")
             .default_size(egui::Vec2::new(180., 1.))
             .show(ctx, |ui| {
                 if ui.button("New Raymarcher").clicked() {
                     self.renderers.push(Box::new(Raymarcher::new(
                         device,
-                        self.cur_window_size,
+                        self.cur_texture_size,
                         wgpu::TextureFormat::Rgba16Float,
                         format!("Raymarcher {}", self.renderers.len()),
                     )));
@@ -125,14 +399,21 @@ impl GI {
                 if ui.button("New Radiance Cascades").clicked() {
                     self.renderers.push(Box::new(RadianceCascades::new(
                         device,
-                        self.cur_window_size,
+                        self.cur_texture_size,
                         format!("RC {}", self.renderers.len()),
                     )));
                 }
+                if ui.button("New Path Tracer").clicked() {
+                    self.renderers.push(Box::new(PathTracer::new(
+                        device,
+                        self.cur_texture_size,
+                        format!("Path Tracer {}", self.renderers.len()),
+                    )));
+                }
                 if ui.button("New Texture Renderer").clicked() {
                     self.renderers.push(Box::new(TextureRenderer::new(
                         device,
-                        self.cur_window_size,
+                        self.cur_texture_size,
                         format!("Texture {}", self.renderers.len()),
                     )));
                 }
@@ -148,7 +429,7 @@ impl GI {
                 }
             });
 
-        match self.cur_renderer {
+        let renderer_changed = match self.cur_renderer {
             CurRenderer::Diff => {
                 egui::Window::new("Difference")
                     .default_size(egui::Vec2::new(1., 1.))
@@ -201,16 +482,27 @@ impl GI {
                             difference::DiffMode::Second,
                             "Second",
                         );
+
+                        ui.heading("Metrics");
+                        if self.difference.metrics.render_egui(ui) {
+                            // only the measured area changed, the textures are still valid
+                            self.difference.metrics.compute(device, queue, None);
+                        }
                     });
 
+                let mut changed = false;
                 if self.diff_indices.0 < self.renderers.len() {
-                    self.renderers[self.diff_indices.0].render_egui(ctx, device, queue);
+                    changed |= self.renderers[self.diff_indices.0].render_egui(ctx, device, queue);
                 }
                 if self.diff_indices.1 < self.renderers.len() {
-                    self.renderers[self.diff_indices.1].render_egui(ctx, device, queue);
+                    changed |= self.renderers[self.diff_indices.1].render_egui(ctx, device, queue);
                 }
+                changed
             }
             CurRenderer::Index(i) => self.renderers[i].render_egui(ctx, device, queue),
         };
+
+        renderer_changed
+            || old_state != (self.cur_renderer, self.diff_indices, self.difference.config)
     }
 }
@@ -0,0 +1,389 @@
+use super::distance_field::DistanceField;
+use super::GIRenderer;
+use super::RendererSession;
+use super::SRGB_WGSL;
+use crate::profiler::GpuProfiler;
+use crate::shaders::{shader_file, ComputePipeline, ShaderFile};
+use egui_wgpu::wgpu;
+use std::cell::Cell;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct RawUniformData {
+    ray_count: u32,
+    frame: u32,
+    samples: u32,
+    _pad: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PathTracerConfig {
+    pub rays_per_frame: u32,
+    /// stops accumulating after this many rays per pixel
+    pub max_samples: u32,
+}
+
+impl Default for PathTracerConfig {
+    fn default() -> Self {
+        PathTracerConfig {
+            rays_per_frame: 16,
+            max_samples: 4096,
+        }
+    }
+}
+
+/// ground truth renderer, accumulates randomly jittered rays over frames
+///
+/// converges to what the raymarcher gives with infinitely many rays, the
+/// accumulation starts over on `reset`
+pub struct PathTracer {
+    pub label: String,
+
+    config: PathTracerConfig,
+    texture_size: (u32, u32),
+
+    uniform_buffer: wgpu::Buffer,
+    // ping-ponged, one holds the sums so far and the other gets the new ones
+    accum_textures: [wgpu::Texture; 2],
+
+    uniform_bind_group: wgpu::BindGroup,
+
+    // sdf, in, accum in, accum out, out
+    textures_bgl: wgpu::BindGroupLayout,
+
+    pipeline: ComputePipeline,
+
+    samples: Cell<u32>,
+    frame: Cell<u32>,
+}
+
+impl PathTracer {
+    const ACCUM_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+    const SHADER: ShaderFile = shader_file!("src/gi/path_tracer/shader.wgsl");
+
+    fn create_texture(
+        device: &wgpu::Device,
+        size: (u32, u32),
+        label: &str,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    fn create_accum_textures(device: &wgpu::Device, size: (u32, u32)) -> [wgpu::Texture; 2] {
+        let ct = || {
+            PathTracer::create_texture(
+                device,
+                size,
+                "path tracer accumulation texture",
+                PathTracer::ACCUM_FORMAT,
+            )
+        };
+        [ct(), ct()]
+    }
+
+    pub fn new(device: &wgpu::Device, texture_size: (u32, u32), label: String) -> Self {
+        PathTracer::with_config(device, texture_size, label, PathTracerConfig::default())
+    }
+
+    pub fn with_config(
+        device: &wgpu::Device,
+        texture_size: (u32, u32),
+        label: String,
+        config: PathTracerConfig,
+    ) -> Self {
+        let nearest_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("path tracer nearest sampler"),
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("path tracer uniform buffer"),
+            size: std::mem::size_of::<RawUniformData>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let accum_textures = PathTracer::create_accum_textures(device, texture_size);
+
+        let uniform_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("path tracer uniform bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("path tracer uniform bind group"),
+            layout: &uniform_bgl,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&nearest_sampler),
+                },
+            ],
+        });
+
+        let texture_entry = |binding, filterable| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let storage_entry = |binding, format| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        let textures_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("path tracer texture bind group layout"),
+            entries: &[
+                texture_entry(0, true),
+                texture_entry(1, true),
+                // rgba32float can't be filtered everywhere
+                texture_entry(2, false),
+                storage_entry(3, PathTracer::ACCUM_FORMAT),
+                storage_entry(4, wgpu::TextureFormat::Rgba16Float),
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("path tracer pipeline layout"),
+            bind_group_layouts: &[&uniform_bgl, &textures_bgl],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = ComputePipeline::with_prefix(
+            device,
+            "path tracer pipeline",
+            PathTracer::SHADER,
+            SRGB_WGSL,
+            pipeline_layout,
+        );
+
+        PathTracer {
+            label,
+
+            config,
+            texture_size,
+
+            uniform_buffer,
+            accum_textures,
+            uniform_bind_group,
+
+            textures_bgl,
+
+            pipeline,
+
+            samples: Cell::new(0),
+            frame: Cell::new(0),
+        }
+    }
+
+    fn create_texture_bind_group(
+        &self,
+        device: &wgpu::Device,
+        sdf_view: &wgpu::TextureView,
+        in_texture_view: &wgpu::TextureView,
+        out_texture_view: &wgpu::TextureView,
+        accum_index: usize,
+    ) -> wgpu::BindGroup {
+        let view =
+            |texture: &wgpu::Texture| texture.create_view(&wgpu::TextureViewDescriptor::default());
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("path tracer textures bind group"),
+            layout: &self.textures_bgl,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(sdf_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(in_texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&view(
+                        &self.accum_textures[1 - accum_index],
+                    )),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&view(
+                        &self.accum_textures[accum_index],
+                    )),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(out_texture_view),
+                },
+            ],
+        })
+    }
+}
+
+impl GIRenderer for PathTracer {
+    fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        in_texture: &wgpu::Texture,
+        sdf: &DistanceField,
+        out_texture: &wgpu::Texture,
+        profiler: &GpuProfiler,
+    ) {
+        let samples = self.samples.get();
+        // once converged the pass only writes the average again,
+        // without a ray per frame it would never get there, sessions can ask for 0
+        let ray_count = u32::min(
+            self.config.rays_per_frame.max(1),
+            self.config.max_samples.saturating_sub(samples),
+        );
+        let frame = self.frame.get();
+
+        let uniform_data = RawUniformData {
+            ray_count,
+            frame,
+            samples,
+            _pad: 0,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform_data));
+
+        let in_view = in_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let out_view = out_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let textures_bind_group = self.create_texture_bind_group(
+            device,
+            sdf.view(),
+            &in_view,
+            &out_view,
+            (frame % 2) as usize,
+        );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("path tracer compute pass"),
+                timestamp_writes: profiler.compute_pass_timestamps("path tracer"),
+            });
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+            compute_pass.set_bind_group(1, &textures_bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                u32::div_ceil(self.texture_size.0, 16),
+                u32::div_ceil(self.texture_size.1, 16),
+                1,
+            );
+        }
+
+        queue.submit(Some(encoder.finish()));
+
+        self.samples.set(samples + ray_count);
+        self.frame.set(frame.wrapping_add(1));
+    }
+
+    fn render_egui(&mut self, ctx: &egui::Context, _: &wgpu::Device, _: &wgpu::Queue) -> bool {
+        let old_config = self.config;
+        let mut restart = false;
+        egui::Window::new(&self.label).show(ctx, |ui| {
+            ui.heading("Rays per frame");
+            ui.add(egui::Slider::new(&mut self.config.rays_per_frame, 1..=256).logarithmic(true));
+            ui.heading("Max samples");
+            ui.add(egui::Slider::new(&mut self.config.max_samples, 16..=1 << 20).logarithmic(true));
+
+            ui.separator();
+            ui.label(format!(
+                "Samples: {} / {}",
+                self.samples.get(),
+                self.config.max_samples
+            ));
+            ui.add(egui::ProgressBar::new(
+                self.samples.get() as f32 / self.config.max_samples as f32,
+            ));
+            restart = ui.button("Restart").clicked();
+        });
+        // raising the limit keeps going from the current samples
+        let restart_needed = self.config.rays_per_frame != old_config.rays_per_frame
+            || self.config.max_samples < old_config.max_samples;
+        restart || restart_needed
+    }
+
+    fn reset(&mut self) {
+        self.samples.set(0);
+    }
+
+    fn converging(&self) -> bool {
+        self.samples.get() < self.config.max_samples
+    }
+
+    fn reload_shaders(&mut self, device: &wgpu::Device) -> Result<(), String> {
+        self.pipeline.reload(device)
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, new_size: (u32, u32)) {
+        if new_size == self.texture_size {
+            return;
+        }
+        self.texture_size = new_size;
+        self.accum_textures = PathTracer::create_accum_textures(device, new_size);
+        self.samples.set(0);
+    }
+
+    fn label(&self) -> String {
+        self.label.clone()
+    }
+
+    fn session(&self) -> RendererSession {
+        RendererSession::PathTracer {
+            label: self.label.clone(),
+            config: self.config,
+        }
+    }
+}
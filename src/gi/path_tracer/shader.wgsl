@@ -0,0 +1,99 @@
+const tau = 6.283185307179586;
+
+struct uniform_data {
+    // rays traced per pixel this frame
+    ray_count: u32,
+    // seeds the random numbers, different every frame
+    frame: u32,
+    // samples already in the accumulation texture, 0 after a reset
+    samples: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: uniform_data;
+@group(0) @binding(1)
+var nearest_sampler: sampler;
+
+@group(1) @binding(0)
+var sdf_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var in_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var accum_in: texture_2d<f32>;
+@group(1) @binding(3)
+var accum_out: texture_storage_2d<rgba32float, write>;
+@group(1) @binding(4)
+var out_texture: texture_storage_2d<rgba16float, write>;
+
+fn to_tex(pos: vec2f, texel: vec2f) -> vec2f {
+    return (pos + vec2f(0.5)) * texel;
+}
+
+fn out_of_bounds(pos: vec2f, dims: vec2u) -> bool {
+    return (pos.x < 0. || pos.y < 0. || pos.x >= f32(dims.x) || pos.y >= f32(dims.y));
+}
+
+// pcg hash from "hash functions for gpu rendering", jarzynski and olano
+fn pcg(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random(seed: ptr<function, u32>) -> f32 {
+    *seed = pcg(*seed);
+    return f32(*seed >> 8u) / 16777216.;
+}
+
+// same march as the raymarcher, so both converge to the same image
+fn march_ray(start_pos: vec2f, dir: vec2f) -> vec4f {
+    let in_texture_dims = textureDimensions(in_texture);
+    let texel = vec2f(1.) / vec2f(in_texture_dims);
+    var pos = start_pos;
+
+    for (var step = 0u; step < 1024u; step += 1u) {
+        let dist = textureSampleLevel(sdf_texture, nearest_sampler, to_tex(pos, texel), 0.).r;
+
+        if dist < 1 {
+            let color = textureSampleLevel(in_texture, nearest_sampler, to_tex(pos, texel), 0.);
+            if color.a > 0.99 {
+                return vec4f(srgb_to_linear(color.rgb), color.a);
+            }
+        }
+
+        pos += dir * dist * 0.9;
+
+        if out_of_bounds(pos, in_texture_dims) {
+            return vec4f(0.);
+        }
+    }
+
+    return vec4f(0.);
+}
+
+@compute
+@workgroup_size(16, 16)
+fn main(@builtin(global_invocation_id) id: vec3u) {
+    let pixel_pos = id.xy;
+    if any(pixel_pos >= textureDimensions(out_texture)) {
+        return;
+    }
+
+    var seed = pcg(pixel_pos.x + pcg(pixel_pos.y + pcg(uniforms.frame)));
+
+    // stratified: every ray gets a random angle within its own slice of the circle
+    var sum = vec3f(0.);
+    for (var i = 0u; i < uniforms.ray_count; i += 1u) {
+        let angle = (f32(i) + random(&seed)) * tau / f32(uniforms.ray_count);
+        let dir = vec2f(cos(angle), sin(angle));
+        sum += march_ray(vec2f(pixel_pos), dir).rgb;
+    }
+
+    if uniforms.samples > 0u {
+        sum += textureLoad(accum_in, pixel_pos, 0).rgb;
+    }
+    textureStore(accum_out, pixel_pos, vec4f(sum, 1.));
+
+    let samples = uniforms.samples + uniforms.ray_count;
+    textureStore(out_texture, pixel_pos, vec4f(sum / f32(max(samples, 1u)), 1.));
+}
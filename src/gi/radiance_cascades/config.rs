@@ -10,6 +10,8 @@ pub struct RawUniformData {
     pub ringing_fix: u32,
     pub num_cascades: u32,
     pub cur_cascade: u32,
+    // bit i set if cascade i is shown
+    pub cascade_mask: u32,
 }
 
 impl From<RCConfig> for RawUniformData {
@@ -25,17 +27,18 @@ impl From<RCConfig> for RawUniformData {
             ringing_fix: config.ringing_fix as u32,
             num_cascades: config.num_cascades,
             cur_cascade: 0,
+            cascade_mask: u32::MAX,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub enum ProbeLayout {
     Offset = 0,
     Stacked = 1,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub enum RingingFix {
     Vanilla = 0,
     Bilinear = 1,
@@ -54,7 +57,62 @@ impl std::fmt::Display for RingingFix {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// where the cascades live on the gpu
+#[derive(Debug, Default, PartialEq, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub enum CascadeStorage {
+    /// a flat storage buffer, limited by the max buffer binding size
+    #[default]
+    Buffer,
+    /// 2d array storage textures, for cascades too big for a buffer
+    Texture,
+}
+
+impl std::fmt::Display for CascadeStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                CascadeStorage::Buffer => "Buffer",
+                CascadeStorage::Texture => "Texture array",
+            }
+        )
+    }
+}
+
+/// the float format of the cascade values
+#[derive(Debug, Default, PartialEq, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub enum CascadePrecision {
+    #[default]
+    Half,
+    /// twice the memory, for bright emitters and checking quantization
+    Full,
+}
+
+impl CascadePrecision {
+    /// bytes per cascade element, f16 values are packed in a vec2<u32>
+    pub fn elem_size(&self) -> u32 {
+        match self {
+            CascadePrecision::Half => 8,
+            CascadePrecision::Full => 16,
+        }
+    }
+}
+
+impl std::fmt::Display for CascadePrecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                CascadePrecision::Half => "f16",
+                CascadePrecision::Full => "f32",
+            }
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct RCConfig {
     pub c0_rays: u32,
     pub c0_spacing: f32,
@@ -67,18 +125,45 @@ pub struct RCConfig {
     pub ringing_fix: RingingFix,
 
     pub num_cascades: u32,
+
+    // sessions saved before these existed use f16 buffers
+    #[serde(default)]
+    pub storage: CascadeStorage,
+    #[serde(default)]
+    pub precision: CascadePrecision,
 }
 
 impl RCConfig {
+    /// the sliders keep these in range, configs from the command line or a session are checked here
+    pub fn validate(&self) -> Result<(), String> {
+        // the ray offsets divide by the scalings minus 1
+        if self.angular_scaling <= 1 {
+            return Err(format!(
+                "Angular scaling has to be above 1, got {}",
+                self.angular_scaling
+            ));
+        }
+        if self.spatial_scaling <= 1. || self.spatial_scaling.is_nan() {
+            return Err(format!(
+                "Spatial scaling has to be above 1, got {}",
+                self.spatial_scaling
+            ));
+        }
+        if self.num_cascades == 0 {
+            return Err("There has to be at least 1 cascade".to_owned());
+        }
+        Ok(())
+    }
+
     pub fn get_spatial_resolution(
         &self,
-        window_size: (u32, u32),
+        texture_size: (u32, u32),
         cascade_index: u32,
     ) -> (u32, u32) {
         let probe_spacing = self.c0_spacing * f32::powi(self.spatial_scaling, cascade_index as i32);
         let float_result = (
-            window_size.0 as f32 / probe_spacing,
-            window_size.1 as f32 / probe_spacing,
+            texture_size.0 as f32 / probe_spacing,
+            texture_size.1 as f32 / probe_spacing,
         );
         (
             float_result.0.ceil() as u32 + 1,
@@ -86,28 +171,96 @@ impl RCConfig {
         )
     }
 
-    pub fn get_num_probes_1d(&self, window_size: (u32, u32), cascade_num: u32) -> u32 {
-        let spa_res = self.get_spatial_resolution(window_size, cascade_num);
+    pub fn get_num_probes_1d(&self, texture_size: (u32, u32), cascade_num: u32) -> u32 {
+        let spa_res = self.get_spatial_resolution(texture_size, cascade_num);
         spa_res.0 * spa_res.1
     }
 
-    pub fn get_cascade_size(&self, window_size: (u32, u32), cascade_index: u32) -> u32 {
+    pub fn get_cascade_size(&self, texture_size: (u32, u32), cascade_index: u32) -> u32 {
         let num_rays = match cascade_index {
             0 => 1,
             _ => self.c0_rays * u32::pow(self.angular_scaling, cascade_index - 1),
         };
 
-        let res = u32::checked_mul(num_rays, self.get_num_probes_1d(window_size, cascade_index));
-        res.unwrap_or(std::u32::MAX)
+        let res = u32::checked_mul(
+            num_rays,
+            self.get_num_probes_1d(texture_size, cascade_index),
+        );
+        res.unwrap_or(u32::MAX)
+    }
+
+    // the functions below mirror the ones with the same names in shader.wgsl
+
+    pub fn cascade_probe_spacing(&self, cascade_index: u32) -> f32 {
+        self.c0_spacing * f32::powi(self.spatial_scaling, cascade_index as i32)
+    }
+
+    pub fn cascade_angular_resolution(&self, cascade_index: u32) -> u32 {
+        self.c0_rays
+            .saturating_mul(self.angular_scaling.saturating_pow(cascade_index))
     }
 
-    pub fn get_max_cascade_size(&self, window_size: (u32, u32)) -> u32 {
+    pub fn cascade_ray_length(&self, cascade_index: u32) -> f32 {
+        self.c0_raylength * f32::powi(self.angular_scaling as f32, cascade_index as i32)
+    }
+
+    pub fn cascade_ray_offset(&self, cascade_index: u32) -> f32 {
+        let scaling = self.angular_scaling as f32;
+        self.c0_raylength * (f32::powi(scaling, cascade_index as i32) - 1.) / (scaling - 1.)
+    }
+
+    /// in pixels
+    pub fn probe_position_from_index(
+        &self,
+        cascade_index: u32,
+        probe_index: (u32, u32),
+    ) -> (f32, f32) {
+        let spacing = self.cascade_probe_spacing(cascade_index);
+        let shift = match self.probe_layout {
+            ProbeLayout::Offset => {
+                0.5 * (f32::powi(self.spatial_scaling, cascade_index as i32) - 1.)
+                    / (self.spatial_scaling - 1.)
+                    * self.c0_spacing
+            }
+            ProbeLayout::Stacked => 0.,
+        };
+        (
+            0.5 + spacing * probe_index.0 as f32 - shift,
+            0.5 + spacing * probe_index.1 as f32 - shift,
+        )
+    }
+
+    /// the probe closest to a position in pixels
+    pub fn nearest_probe(
+        &self,
+        texture_size: (u32, u32),
+        cascade_index: u32,
+        pos: (f32, f32),
+    ) -> (u32, u32) {
+        let spacing = self.cascade_probe_spacing(cascade_index);
+        let origin = self.probe_position_from_index(cascade_index, (0, 0));
+        let spatial = self.get_spatial_resolution(texture_size, cascade_index);
+        let index = |pos: f32, origin: f32, count: u32| {
+            (((pos - origin) / spacing).round().max(0.) as u32).min(count - 1)
+        };
+        (
+            index(pos.0, origin.0, spatial.0),
+            index(pos.1, origin.1, spatial.1),
+        )
+    }
+
+    pub fn get_max_cascade_size(&self, texture_size: (u32, u32)) -> u32 {
         (0..self.num_cascades)
-            .map(|cascade_index| self.get_cascade_size(window_size, cascade_index))
+            .map(|cascade_index| self.get_cascade_size(texture_size, cascade_index))
             .max()
             // TODO better error handling
             .unwrap_or(0)
     }
+
+    /// bytes taken by both ping-ponged cascades, without texture padding
+    pub fn get_cascade_memory(&self, texture_size: (u32, u32)) -> u64 {
+        2 * self.get_max_cascade_size(texture_size) as u64 * self.precision.elem_size() as u64
+    }
 }
 
 impl Default for RCConfig {
@@ -124,6 +277,9 @@ impl Default for RCConfig {
             ringing_fix: RingingFix::Bilinear,
 
             num_cascades: 7,
+
+            storage: CascadeStorage::Buffer,
+            precision: CascadePrecision::Half,
         }
     }
 }
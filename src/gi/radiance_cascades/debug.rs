@@ -0,0 +1,444 @@
+use super::config::{CascadePrecision, CascadeStorage, RCConfig};
+use super::resources::Cascades;
+use super::storage::{self, Access};
+use crate::gi::distance_field::DistanceField;
+use crate::profiler::GpuProfiler;
+use crate::shaders::{shader_file, ComputePipeline};
+use egui_wgpu::wgpu;
+
+/// what the rc renderer outputs instead of the final image
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DebugSource {
+    Final,
+    JfaSeeds,
+    Sdf,
+    Cascade(u32),
+}
+
+impl std::fmt::Display for DebugSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DebugSource::Final => write!(f, "Final image"),
+            DebugSource::JfaSeeds => write!(f, "JFA seeds"),
+            DebugSource::Sdf => write!(f, "SDF"),
+            DebugSource::Cascade(i) => write!(f, "Cascade {}", i),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CascadeLayout {
+    /// every probe is a block of its directions
+    ProbeGrid = 0,
+    /// every direction is a tile holding all probes
+    DirectionAtlas = 1,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DebugConfig {
+    pub source: DebugSource,
+    pub cascade_layout: CascadeLayout,
+    /// shown value is value * scale + offset
+    pub scale: f32,
+    pub offset: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct RawUniformData {
+    source: u32,
+    cascade_layout: u32,
+    scale: f32,
+    offset: f32,
+
+    spatial_x: u32,
+    spatial_y: u32,
+    num_dirs: u32,
+    columns: u32,
+    fit: f32,
+    _pad: [u32; 3],
+}
+
+/// shows the jfa and cascade buffers of an rc renderer in its output texture
+pub struct DebugView {
+    pub config: DebugConfig,
+
+    // the shown cascade, copied out of the ping-ponged cascades while rendering
+    cascade_copy: Option<wgpu::Buffer>,
+    // bound instead while no cascade is shown
+    empty_buffer: wgpu::Buffer,
+
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    // the copy is read with the precision of the cascades it comes from
+    precision: CascadePrecision,
+    pipeline: ComputePipeline,
+}
+
+impl DebugView {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("rc debug uniform buffer"),
+            size: std::mem::size_of::<RawUniformData>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("rc debug bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba16Float,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let precision = CascadePrecision::default();
+        let pipeline = DebugView::create_pipeline(device, &bind_group_layout, precision);
+
+        // big enough for one element of either precision
+        let empty_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("rc debug empty buffer"),
+            size: CascadePrecision::Full.elem_size() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        DebugView {
+            config: DebugConfig {
+                source: DebugSource::Final,
+                cascade_layout: CascadeLayout::DirectionAtlas,
+                scale: 1.,
+                offset: 0.,
+            },
+
+            cascade_copy: None,
+            empty_buffer,
+
+            uniform_buffer,
+            bind_group_layout,
+            precision,
+            pipeline,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        precision: CascadePrecision,
+    ) -> ComputePipeline {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("rc debug pipeline layout"),
+            bind_group_layouts: &[bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // the copy is always a buffer, whatever the cascades are stored in
+        ComputePipeline::with_prefix(
+            device,
+            "rc debug pipeline",
+            shader_file!("src/gi/radiance_cascades/debug_shader.wgsl"),
+            storage::cascade_binding(
+                CascadeStorage::Buffer,
+                precision,
+                0,
+                2,
+                "cascade",
+                Access::Read,
+            ),
+            pipeline_layout,
+        )
+    }
+
+    pub fn reload_shaders(&mut self, device: &wgpu::Device) -> Result<(), String> {
+        self.pipeline.reload(device)
+    }
+
+    /// the cascade that has to be copied with `copy_cascade` while rendering
+    pub fn shown_cascade(&self) -> Option<u32> {
+        match self.config.source {
+            DebugSource::Cascade(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    /// the texture region holding the first `num_elems` elements, whole rows or layers
+    fn texture_copy_size(texture: &wgpu::Texture, num_elems: u32) -> wgpu::Extent3d {
+        let rows = u32::div_ceil(num_elems, texture.width());
+        wgpu::Extent3d {
+            width: texture.width(),
+            height: rows.min(texture.height()),
+            depth_or_array_layers: u32::div_ceil(rows, texture.height()),
+        }
+    }
+
+    /// makes room for the shown cascade, call after the config or the texture size changes
+    pub fn update_buffer(
+        &mut self,
+        device: &wgpu::Device,
+        rc_config: &RCConfig,
+        texture_size: (u32, u32),
+        cascades: &Cascades,
+    ) {
+        if rc_config.precision != self.precision {
+            self.precision = rc_config.precision;
+            self.pipeline =
+                DebugView::create_pipeline(device, &self.bind_group_layout, self.precision);
+        }
+
+        let Some(cascade) = self.shown_cascade() else {
+            self.cascade_copy = None;
+            return;
+        };
+        let cascade_size = rc_config.get_cascade_size(texture_size, cascade);
+        // textures are copied by the row, so the copy can have some elements past the cascade
+        let num_elems = match cascades {
+            Cascades::Buffers(_) => cascade_size as u64,
+            Cascades::Textures(textures, _) => {
+                let copy_size = DebugView::texture_copy_size(&textures[0], cascade_size);
+                copy_size.width as u64
+                    * copy_size.height as u64
+                    * copy_size.depth_or_array_layers as u64
+            }
+        };
+        let size = num_elems * rc_config.precision.elem_size() as u64;
+        if self.cascade_copy.as_ref().is_some_and(|b| b.size() == size) {
+            return;
+        }
+        self.cascade_copy = Some(device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("rc debug cascade buffer"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+    }
+
+    /// copies the cascade that was just written to the ith of `cascades`
+    pub fn copy_cascade(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        cascades: &Cascades,
+        index: usize,
+        cascade_size: u32,
+    ) {
+        let Some(copy) = &self.cascade_copy else {
+            return;
+        };
+        match cascades {
+            Cascades::Buffers(buffers) => {
+                encoder.copy_buffer_to_buffer(&buffers[index], 0, copy, 0, copy.size());
+            }
+            Cascades::Textures(textures, _) => {
+                let texture = &textures[index];
+                // the rows are aligned, so they land back to back like in a cascade buffer
+                encoder.copy_texture_to_buffer(
+                    texture.as_image_copy(),
+                    wgpu::ImageCopyBuffer {
+                        buffer: copy,
+                        layout: wgpu::ImageDataLayout {
+                            offset: 0,
+                            bytes_per_row: Some(texture.width() * self.precision.elem_size()),
+                            rows_per_image: Some(texture.height()),
+                        },
+                    },
+                    DebugView::texture_copy_size(texture, cascade_size),
+                );
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        rc_config: &RCConfig,
+        sdf: &DistanceField,
+        out_texture: &wgpu::Texture,
+        profiler: &GpuProfiler,
+    ) {
+        let texture_size = (out_texture.width(), out_texture.height());
+
+        let mut uniform_data = RawUniformData {
+            source: 0,
+            cascade_layout: self.config.cascade_layout as u32,
+            scale: self.config.scale,
+            offset: self.config.offset,
+
+            spatial_x: 0,
+            spatial_y: 0,
+            num_dirs: 0,
+            columns: 1,
+            fit: 1.,
+            _pad: [0; 3],
+        };
+        let source_texture = match self.config.source {
+            DebugSource::Final => return,
+            DebugSource::JfaSeeds => sdf.seed_texture(),
+            DebugSource::Sdf => {
+                uniform_data.source = 1;
+                sdf.texture()
+            }
+            DebugSource::Cascade(cascade) => {
+                let spatial = rc_config.get_spatial_resolution(texture_size, cascade);
+                let num_dirs = rc_config.get_cascade_size(texture_size, cascade)
+                    / rc_config.get_num_probes_1d(texture_size, cascade);
+                let columns = f32::sqrt(num_dirs as f32).ceil() as u32;
+                let rows = u32::div_ceil(num_dirs, columns);
+                // both layouts take up the same space
+                let layout_size = (spatial.0 * columns, spatial.1 * rows);
+
+                uniform_data.source = 2;
+                uniform_data.spatial_x = spatial.0;
+                uniform_data.spatial_y = spatial.1;
+                uniform_data.num_dirs = num_dirs;
+                uniform_data.columns = columns;
+                uniform_data.fit = f32::max(
+                    1.,
+                    f32::max(
+                        layout_size.0 as f32 / texture_size.0 as f32,
+                        layout_size.1 as f32 / texture_size.1 as f32,
+                    ),
+                );
+                // not read in this mode, any texture does
+                sdf.texture()
+            }
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform_data));
+
+        let cascade_buffer = self.cascade_copy.as_ref().unwrap_or(&self.empty_buffer);
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("rc debug bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &source_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: cascade_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(
+                        &out_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+            ],
+        });
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("rc debug pass"),
+            timestamp_writes: profiler.compute_pass_timestamps("rc debug view"),
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &bind_group, &[]);
+        compute_pass.dispatch_workgroups(
+            u32::div_ceil(texture_size.0, 16),
+            u32::div_ceil(texture_size.1, 16),
+            1,
+        );
+    }
+
+    /// returns whether the config changed
+    pub fn render_egui(
+        &mut self,
+        ui: &mut egui::Ui,
+        num_cascades: u32,
+        texture_size: (u32, u32),
+    ) -> bool {
+        let old_config = self.config;
+
+        egui::ComboBox::from_id_source("rc debug source")
+            .selected_text(format!("{}", self.config.source))
+            .show_ui(ui, |ui| {
+                let sources = [DebugSource::Final, DebugSource::JfaSeeds, DebugSource::Sdf]
+                    .into_iter()
+                    .chain((0..num_cascades).map(DebugSource::Cascade));
+                for source in sources {
+                    ui.selectable_value(&mut self.config.source, source, format!("{}", source));
+                }
+            });
+
+        if self.config.source != old_config.source {
+            // something that shows the usual range of every source
+            self.config.scale = match self.config.source {
+                DebugSource::JfaSeeds => 1. / texture_size.0.max(texture_size.1) as f32,
+                DebugSource::Sdf => 1. / 64.,
+                _ => 1.,
+            };
+            self.config.offset = 0.;
+        }
+
+        if self.config.source != DebugSource::Final {
+            if self.shown_cascade().is_some() {
+                ui.columns(2, |columns| {
+                    columns[0].radio_value(
+                        &mut self.config.cascade_layout,
+                        CascadeLayout::ProbeGrid,
+                        "Probe grid",
+                    );
+                    columns[1].radio_value(
+                        &mut self.config.cascade_layout,
+                        CascadeLayout::DirectionAtlas,
+                        "Direction atlas",
+                    );
+                });
+            }
+            ui.add(
+                egui::Slider::new(&mut self.config.scale, 1e-4..=1e4)
+                    .logarithmic(true)
+                    .text("Scale"),
+            );
+            ui.add(egui::Slider::new(&mut self.config.offset, -1.0..=1.).text("Offset"));
+        }
+
+        self.config != old_config
+    }
+}
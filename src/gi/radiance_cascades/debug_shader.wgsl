@@ -0,0 +1,82 @@
+const nonexistent_coord: f32 = -65504.;
+
+struct uniform_data {
+    // 0 - jfa seeds, 1 - sdf, 2 - cascade
+    source: u32,
+    // 0 - probe grid, 1 - direction atlas
+    cascade_layout: u32,
+    scale: f32,
+    offset: f32,
+
+    // probes per axis of the cascade
+    spatial_x: u32,
+    spatial_y: u32,
+    num_dirs: u32,
+    // directions per row of a probe block, or direction tiles per row of the atlas
+    columns: u32,
+    // cascade texels per output pixel, the layout is shrunk if it doesn't fit
+    fit: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: uniform_data;
+@group(0) @binding(1)
+var source_texture: texture_2d<f32>;
+@group(0) @binding(3)
+var out_texture: texture_storage_2d<rgba16float, write>;
+// `cascade` at binding 2 is prepended by debug.rs
+
+// the cascade texel shown at `cell`, zero outside of the layout
+fn cascade_texel(cell: vec2u) -> vec3f {
+    let spatial = vec2u(uniforms.spatial_x, uniforms.spatial_y);
+    let rows = (uniforms.num_dirs + uniforms.columns - 1u) / uniforms.columns;
+
+    var probe: vec2u;
+    var dir_pos: vec2u;
+    if uniforms.cascade_layout == 0u {
+        // every probe is a block of its directions
+        let block = vec2u(uniforms.columns, rows);
+        probe = cell / block;
+        dir_pos = cell % block;
+    } else {
+        // every direction is a tile holding all probes
+        probe = cell % spatial;
+        dir_pos = cell / spatial;
+    }
+
+    let dir = dir_pos.y * uniforms.columns + dir_pos.x;
+    if any(probe >= spatial) || dir_pos.x >= uniforms.columns || dir >= uniforms.num_dirs {
+        return vec3f(0.);
+    }
+
+    let index = dir * spatial.x * spatial.y + probe.y * spatial.x + probe.x;
+    if index >= arrayLength(&cascade) {
+        return vec3f(0.);
+    }
+    return read_cascade(index).rgb;
+}
+
+@compute
+@workgroup_size(16, 16)
+fn main(@builtin(global_invocation_id) id: vec3u) {
+    let pixel_pos = id.xy;
+    if any(pixel_pos >= textureDimensions(out_texture)) {
+        return;
+    }
+
+    var value: vec3f;
+    if uniforms.source == 0u {
+        let seed = textureLoad(source_texture, pixel_pos, 0).xy;
+        if seed.x == nonexistent_coord {
+            textureStore(out_texture, pixel_pos, vec4f(0., 0., 0., 1.));
+            return;
+        }
+        value = vec3f(seed, 0.);
+    } else if uniforms.source == 1u {
+        value = vec3f(textureLoad(source_texture, pixel_pos, 0).r);
+    } else {
+        value = cascade_texel(vec2u(vec2f(pixel_pos) * uniforms.fit));
+    }
+
+    textureStore(out_texture, pixel_pos, vec4f(value * uniforms.scale + uniforms.offset, 1.));
+}
@@ -13,20 +13,15 @@ struct uniform_data {
 
     num_cascades: u32,
     cur_cascade: u32,
+    cascade_mask: u32,
 }
 
 @group(0) @binding(0)
 var<uniform> uniforms: uniform_data;
-@group(0) @binding(1)
-var<storage, read> cascade_buffer: array<vec2u>;
+// binding 1 is cascade_0, declared in the prefix with read_cascade_0, see storage.rs
 @group(0) @binding(2)
 var out_texture: texture_storage_2d<rgba16float, write>;
 
-fn read_cascade(pos: u32) -> vec4f {
-    let packed = cascade_buffer[pos];
-    return vec4f(unpack2x16float(packed.x), unpack2x16float(packed.y));
-}
-
 // convert position from 2d to 1d
 fn pos_2d1d(pos2d: vec2u, dims: vec2u) -> u32 {
     return pos2d.x + pos2d.y * dims.x;
@@ -82,7 +77,7 @@ fn get_color(id2d: vec2u) -> vec4f {
         let offset = vec2u(i & 1, i >> 1);
         let prev_index1d = pos_2d1d(clamp(probe_index + offset, vec2u(0), spatial_resolution - 1), spatial_resolution);
 
-        result += weights[i] * read_cascade(prev_index1d);
+        result += weights[i] * read_cascade_0(prev_index1d);
     }
 
     result.a = 1.;
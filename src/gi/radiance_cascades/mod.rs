@@ -1,12 +1,22 @@
-mod config;
+pub mod config;
+mod debug;
+mod overlay;
+mod probe_inspector;
 mod resources;
+mod storage;
 
+use super::distance_field::DistanceField;
 use super::GIRenderer;
-use crate::jfa::JFA;
+use super::RendererSession;
+use crate::profiler::GpuProfiler;
+use crate::shaders;
+use crate::viewport::Viewport;
 use egui_wgpu::wgpu;
 
-use config::RCConfig;
-use config::RawUniformData;
+pub use config::RCConfig;
+use debug::{DebugSource, DebugView};
+use overlay::ProbeOverlay;
+use probe_inspector::ProbeInspector;
 use resources::RCResources;
 
 pub struct RadianceCascades {
@@ -14,29 +24,96 @@ pub struct RadianceCascades {
 
     config: RCConfig,
     gui_config: RCConfig,
-    window_size: (u32, u32),
+    texture_size: (u32, u32),
 
-    jfa: JFA,
     resources: RCResources,
+    debug_view: DebugView,
+    overlay: ProbeOverlay,
+    probe_inspector: ProbeInspector,
+    /// bit i set if cascade i is shown, the intervals of the others are transparent
+    cascade_mask: u32,
+    /// shown after a resize had to change the config, until it's changed again
+    resize_notice: Option<String>,
 }
 
 impl RadianceCascades {
-    pub fn new(device: &wgpu::Device, window_size: (u32, u32), label: String) -> Self {
-        let config = RCConfig::default();
+    pub fn new(device: &wgpu::Device, texture_size: (u32, u32), label: String) -> Self {
+        RadianceCascades::with_config(device, texture_size, label, RCConfig::default())
+    }
 
-        let resources = RCResources::new(device, window_size, config);
-        let jfa = JFA::new(device, window_size);
+    pub fn with_config(
+        device: &wgpu::Device,
+        texture_size: (u32, u32),
+        label: String,
+        config: RCConfig,
+    ) -> Self {
+        let resources = RCResources::new(device, texture_size, config);
+        let debug_view = DebugView::new(device);
+        let probe_inspector = ProbeInspector::new(device);
 
         RadianceCascades {
             label,
 
             config,
             gui_config: config,
-            window_size,
+            texture_size,
 
-            jfa,
             resources,
+            debug_view,
+            overlay: ProbeOverlay::new(),
+            probe_inspector,
+            cascade_mask: u32::MAX,
+            resize_notice: None,
+        }
+    }
+
+    /// whether the cascades of `config` fit in the storage it picks on this device
+    pub fn fits(device: &wgpu::Device, texture_size: (u32, u32), config: &RCConfig) -> bool {
+        config.get_max_cascade_size(texture_size)
+            <= RCResources::max_cascade_size(&device.limits(), config)
+    }
+
+    /// `config` with the smallest probe spacing from its own up that fits,
+    /// anything that fit at some size fits once every cascade is down to 2x2 probes
+    fn fit_probe_spacing(
+        device: &wgpu::Device,
+        texture_size: (u32, u32),
+        mut config: RCConfig,
+    ) -> RCConfig {
+        let max_spacing = texture_size.0.max(texture_size.1) as f32;
+        while !RadianceCascades::fits(device, texture_size, &config)
+            && config.c0_spacing < max_spacing
+        {
+            // the step of the slider
+            config.c0_spacing += 0.25;
         }
+        config
+    }
+
+    /// a toggle per cascade, returns whether the mask changed
+    fn cascade_mask_egui(ui: &mut egui::Ui, mask: &mut u32, num_cascades: u32) -> bool {
+        let old_mask = *mask;
+        ui.horizontal_wrapped(|ui| {
+            for cascade in 0..num_cascades {
+                let bit = 1 << cascade;
+                let response = ui.selectable_label(*mask & bit != 0, cascade.to_string());
+                if response.clicked() {
+                    if ui.input(|i| i.modifiers.shift) {
+                        *mask = bit;
+                    } else {
+                        *mask ^= bit;
+                    }
+                }
+            }
+            if ui.button("Show all").clicked() {
+                *mask = u32::MAX;
+            }
+        });
+        ui.colored_label(
+            egui::Color32::from_rgb(150, 150, 150),
+            "click mutes a cascade, shift+click solos it",
+        );
+        *mask != old_mask
     }
 }
 
@@ -46,41 +123,42 @@ impl GIRenderer for RadianceCascades {
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         in_texture: &wgpu::Texture,
+        sdf: &DistanceField,
         out_texture: &wgpu::Texture,
+        profiler: &GpuProfiler,
     ) {
         let in_view = in_texture.create_view(&wgpu::TextureViewDescriptor::default());
         let out_view = out_texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        self.jfa.render(
-            device,
-            queue,
-            &in_view,
-            &self.resources.sdf_view,
-            (in_texture.size().width, in_texture.size().height),
-        );
+        let in_texture_bind_group =
+            self.resources
+                .create_texture_bind_group(device, &in_view, sdf.view());
 
-        let in_texture_bind_group = self.resources.create_texture_bind_group(device, &in_view);
+        self.resources
+            .write_uniforms(queue, self.config, self.cascade_mask);
 
-        for i in 0..self.config.num_cascades {
-            let mut encoder =
-                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-
-            // TODO: not do this
-            let uniform_data = RawUniformData {
-                cur_cascade: self.config.num_cascades - i - 1,
-                ..RawUniformData::from(self.config)
-            };
-            queue.write_buffer(
-                &self.resources.uniform_buffer,
-                0,
-                bytemuck::bytes_of(&uniform_data),
-            );
+        // every cascade and the final pass in one submission
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("rc encoder"),
+        });
+        let mut inspecting = false;
+        let max_groups_per_dimension = device.limits().max_compute_workgroups_per_dimension;
 
+        for i in 0..self.config.num_cascades {
+            let cascade = self.config.num_cascades - i - 1;
+            let uniform_offset = self.resources.uniform_offset(cascade);
             {
-                let mut compute_pass =
-                    encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("rc cascade pass"),
+                    timestamp_writes: profiler
+                        .compute_pass_timestamps(&format!("rc cascade {}", cascade)),
+                });
                 compute_pass.set_pipeline(&self.resources.main_pipeline);
-                compute_pass.set_bind_group(0, &self.resources.uniform_bind_group, &[]);
+                compute_pass.set_bind_group(
+                    0,
+                    &self.resources.uniform_bind_group,
+                    &[uniform_offset],
+                );
                 compute_pass.set_bind_group(1, &in_texture_bind_group, &[]);
                 compute_pass.set_bind_group(
                     2,
@@ -88,26 +166,61 @@ impl GIRenderer for RadianceCascades {
                     &[],
                 );
 
-                let cascade_size = self
-                    .config
-                    .get_cascade_size(self.window_size, self.config.num_cascades - i - 1);
+                let cascade_size = self.config.get_cascade_size(self.texture_size, cascade);
                 let num_groups = u32::div_ceil(cascade_size, 128);
+                // more rows past the limit, the shader skips the threads after the cascade
+                let groups_x = num_groups.min(max_groups_per_dimension);
+
+                compute_pass.dispatch_workgroups(groups_x, u32::div_ceil(num_groups, groups_x), 1);
+            }
+            if self.debug_view.shown_cascade() == Some(cascade) {
+                self.debug_view.copy_cascade(
+                    &mut encoder,
+                    self.resources.cascades(),
+                    i as usize % 2,
+                    self.config.get_cascade_size(self.texture_size, cascade),
+                );
+            }
 
-                compute_pass.dispatch_workgroups(num_groups, 1, 1);
+            if self.probe_inspector.inspects(cascade, self.overlay.cascade) {
+                inspecting = true;
+                self.probe_inspector.inspect(
+                    device,
+                    queue,
+                    &mut encoder,
+                    &self.config,
+                    &self.resources,
+                    &in_texture_bind_group,
+                    // reads the buffer that was just written as prev_cascade
+                    &self.resources.temp_bind_groups[(i as usize + 1) % 2],
+                    cascade,
+                    self.overlay.probe,
+                    profiler,
+                );
             }
-            queue.submit(Some(encoder.finish()));
         }
 
-        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-        let final_bind_group = self.resources.create_final_bind_group(
-            device,
-            &out_view,
-            1 - (self.config.num_cascades % 2) as usize,
-        );
+        if self.debug_view.config.source != DebugSource::Final {
+            self.debug_view.render(
+                device,
+                queue,
+                &mut encoder,
+                &self.config,
+                sdf,
+                out_texture,
+                profiler,
+            );
+        } else {
+            let final_bind_group = self.resources.create_final_bind_group(
+                device,
+                &out_view,
+                1 - (self.config.num_cascades % 2) as usize,
+            );
 
-        {
-            let mut final_pass =
-                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            let mut final_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("rc final pass"),
+                timestamp_writes: profiler.compute_pass_timestamps("rc final"),
+            });
             final_pass.set_pipeline(&self.resources.final_pipeline);
             final_pass.set_bind_group(0, &final_bind_group, &[]);
             final_pass.dispatch_workgroups(
@@ -118,30 +231,44 @@ impl GIRenderer for RadianceCascades {
         }
 
         queue.submit(Some(encoder.finish()));
+        if inspecting {
+            self.probe_inspector.after_submit();
+        }
     }
 
-    fn render_egui(&mut self, ctx: &egui::Context, device: &wgpu::Device, _: &wgpu::Queue) {
-        let max_cascade_size = {
-            let max_buffer_elems =
-                device.limits().max_buffer_size / RCResources::CASCADE_BUFFER_ELEM_SIZE as u64;
-            let max_workgroups = device.limits().max_compute_workgroups_per_dimension;
-            u32::min(max_buffer_elems as u32, max_workgroups * 128)
-        };
+    fn render_egui(
+        &mut self,
+        ctx: &egui::Context,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> bool {
+        self.probe_inspector.poll(device, queue);
+
+        let fits = RadianceCascades::fits(device, self.texture_size, &self.gui_config);
+        let mut config_changed = false;
+        let mut debug_changed = false;
+        let mut probe_changed = false;
+        let mut mask_changed = false;
 
         egui::Window::new(&self.label)
             .default_size(egui::Vec2::new(1., 1.))
             .show(ctx, |ui| {
-                let size_label_color =
-                    if self.gui_config.get_max_cascade_size(self.window_size) > max_cascade_size {
-                        egui::Color32::from_rgb(255, 0, 0)
-                    } else {
-                        egui::Color32::from_rgb(150, 150, 150)
-                    };
+                if let Some(notice) = &self.resize_notice {
+                    ui.colored_label(egui::Color32::from_rgb(255, 200, 0), notice);
+                }
+
+                let size_label_color = if fits {
+                    egui::Color32::from_rgb(150, 150, 150)
+                } else {
+                    egui::Color32::from_rgb(255, 0, 0)
+                };
                 ui.colored_label(
                     size_label_color,
                     format!(
-                        "Max cascade size: {}",
-                        self.gui_config.get_max_cascade_size(self.window_size)
+                        "Max cascade size: {}\nCascade memory: {:.1} MiB",
+                        self.gui_config.get_max_cascade_size(self.texture_size),
+                        self.gui_config.get_cascade_memory(self.texture_size) as f64
+                            / (1024. * 1024.)
                     ),
                 );
 
@@ -208,26 +335,155 @@ impl GIRenderer for RadianceCascades {
                             "Bilinear",
                         );
                     });
+
+                ui.heading("Cascade storage");
+                ui.columns(2, |columns| {
+                    for (column, storage) in [
+                        config::CascadeStorage::Buffer,
+                        config::CascadeStorage::Texture,
+                    ]
+                    .into_iter()
+                    .enumerate()
+                    {
+                        columns[column].radio_value(
+                            &mut self.gui_config.storage,
+                            storage,
+                            format!("{}", storage),
+                        );
+                    }
+                });
+
+                ui.heading("Precision");
+                ui.columns(2, |columns| {
+                    for (column, precision) in [
+                        config::CascadePrecision::Half,
+                        config::CascadePrecision::Full,
+                    ]
+                    .into_iter()
+                    .enumerate()
+                    {
+                        columns[column].radio_value(
+                            &mut self.gui_config.precision,
+                            precision,
+                            format!("{}", precision),
+                        );
+                    }
+                });
+
+                ui.heading("Shown cascades");
+                mask_changed = RadianceCascades::cascade_mask_egui(
+                    ui,
+                    &mut self.cascade_mask,
+                    self.config.num_cascades,
+                );
+
+                ui.heading("Probe overlay");
+                probe_changed |= self
+                    .overlay
+                    .render_egui(ui, &self.config, self.texture_size);
+
+                ui.heading("Probe inspector");
+                probe_changed |=
+                    self.probe_inspector
+                        .render_egui(ui, &self.config, self.overlay.cascade);
+
+                ui.heading("Debug view");
+                debug_changed =
+                    self.debug_view
+                        .render_egui(ui, self.config.num_cascades, self.texture_size);
             });
 
-        if self.gui_config.get_max_cascade_size(self.window_size) > max_cascade_size {
+        if !fits {
             println!("Config ignored, the cascades are too big");
-            return;
+        } else if self.config != self.gui_config {
+            self.config = self.gui_config;
+            self.resources
+                .update(device, self.texture_size, self.config);
+            self.resize_notice = None;
+            config_changed = true;
         }
 
-        if self.config != self.gui_config {
-            self.config = self.gui_config;
-            self.resources = RCResources::new(device, self.window_size, self.config);
+        if config_changed || debug_changed {
+            if let Some(cascade) = self.debug_view.shown_cascade() {
+                if cascade >= self.config.num_cascades {
+                    self.debug_view.config.source = DebugSource::Final;
+                }
+            }
+            self.debug_view.update_buffer(
+                device,
+                &self.config,
+                self.texture_size,
+                self.resources.cascades(),
+            );
         }
+        if config_changed || probe_changed {
+            self.overlay.cascade = self.overlay.cascade.min(self.config.num_cascades - 1);
+            self.probe_inspector
+                .update_buffers(device, &self.config, self.overlay.cascade);
+        }
+        // the inspector only reads back while rendering
+        config_changed
+            || debug_changed
+            || mask_changed
+            || (probe_changed && self.probe_inspector.enabled)
+    }
+
+    fn pick(&mut self, pos: (f32, f32)) -> bool {
+        self.overlay.pick(&self.config, self.texture_size, pos);
+        self.probe_inspector.enabled
+    }
+
+    fn waiting(&self) -> bool {
+        self.probe_inspector.waiting()
+    }
+
+    fn paint_overlay(&self, painter: &egui::Painter, viewport: &Viewport) {
+        self.overlay
+            .paint(painter, &self.config, self.texture_size, viewport);
+    }
+
+    fn reload_shaders(&mut self, device: &wgpu::Device) -> Result<(), String> {
+        shaders::join_errors([
+            self.resources.reload_shaders(device),
+            self.debug_view.reload_shaders(device),
+        ])
     }
 
     fn resize(&mut self, device: &wgpu::Device, new_size: (u32, u32)) {
-        self.window_size = new_size;
-        self.resources = RCResources::new(device, new_size, self.config);
-        self.jfa = JFA::new(device, new_size);
+        if new_size == self.texture_size {
+            return;
+        }
+        self.texture_size = new_size;
+        if !RadianceCascades::fits(device, new_size, &self.config) {
+            let config = RadianceCascades::fit_probe_spacing(device, new_size, self.config);
+            let notice = format!(
+                "The cascades didn't fit the new size, C0 probe spacing raised from {} to {}",
+                self.config.c0_spacing, config.c0_spacing
+            );
+            println!("{}", notice);
+            self.resize_notice = Some(notice);
+            self.config = config;
+            self.gui_config = config;
+            self.probe_inspector
+                .update_buffers(device, &self.config, self.overlay.cascade);
+        }
+        self.resources.resize(device, new_size, self.config);
+        self.debug_view.update_buffer(
+            device,
+            &self.config,
+            self.texture_size,
+            self.resources.cascades(),
+        );
     }
 
     fn label(&self) -> String {
         self.label.clone()
     }
+
+    fn session(&self) -> RendererSession {
+        RendererSession::RadianceCascades {
+            label: self.label.clone(),
+            config: self.config,
+        }
+    }
 }
@@ -0,0 +1,144 @@
+use super::config::RCConfig;
+use crate::viewport::Viewport;
+
+/// draws the probes of a cascade over the scene, and the ray interval of one of them
+///
+/// also holds the probe the probe inspector reads back
+pub struct ProbeOverlay {
+    pub enabled: bool,
+    pub cascade: u32,
+    /// index of the probe whose rays are drawn
+    pub probe: (u32, u32),
+}
+
+impl ProbeOverlay {
+    // closer than this in window pixels and the probes are just noise
+    const MIN_PROBE_DISTANCE: f32 = 8.;
+    const MAX_DRAWN_RAYS: u32 = 256;
+
+    pub fn new() -> Self {
+        ProbeOverlay {
+            enabled: false,
+            cascade: 0,
+            probe: (0, 0),
+        }
+    }
+
+    /// selects the probe closest to `pos` in pixels
+    pub fn pick(&mut self, config: &RCConfig, texture_size: (u32, u32), pos: (f32, f32)) {
+        self.probe = config.nearest_probe(texture_size, self.cascade, pos);
+    }
+
+    /// returns whether the selected probe changed
+    pub fn render_egui(
+        &mut self,
+        ui: &mut egui::Ui,
+        config: &RCConfig,
+        texture_size: (u32, u32),
+    ) -> bool {
+        let old_selection = (self.cascade, self.probe);
+        ui.checkbox(&mut self.enabled, "Show probe overlay");
+
+        self.cascade = self.cascade.min(config.num_cascades - 1);
+        ui.add(egui::Slider::new(&mut self.cascade, 0..=config.num_cascades - 1).text("Cascade"));
+
+        let spatial = config.get_spatial_resolution(texture_size, self.cascade);
+        self.probe = (
+            self.probe.0.min(spatial.0 - 1),
+            self.probe.1.min(spatial.1 - 1),
+        );
+        ui.horizontal(|ui| {
+            ui.label("Probe");
+            ui.add(egui::DragValue::new(&mut self.probe.0).range(0..=spatial.0 - 1));
+            ui.add(egui::DragValue::new(&mut self.probe.1).range(0..=spatial.1 - 1));
+        });
+
+        let offset = config.cascade_ray_offset(self.cascade);
+        ui.colored_label(
+            egui::Color32::from_rgb(150, 150, 150),
+            format!(
+                "{}x{} probes, {} px apart\n{} rays, from {} to {} px\nthe grid hides when the probes get too close, zoom in",
+                spatial.0,
+                spatial.1,
+                config.cascade_probe_spacing(self.cascade),
+                config.cascade_angular_resolution(self.cascade),
+                offset,
+                offset + config.cascade_ray_length(self.cascade),
+            ),
+        );
+
+        (self.cascade, self.probe) != old_selection
+    }
+
+    pub fn paint(
+        &self,
+        painter: &egui::Painter,
+        config: &RCConfig,
+        texture_size: (u32, u32),
+        viewport: &Viewport,
+    ) {
+        if !self.enabled || self.cascade >= config.num_cascades {
+            return;
+        }
+        let to_window = |pos: (f32, f32)| {
+            let (x, y) = viewport.scene_to_window(pos);
+            egui::pos2(x, y)
+        };
+        let spatial = config.get_spatial_resolution(texture_size, self.cascade);
+        let spacing = config.cascade_probe_spacing(self.cascade);
+
+        if spacing * viewport.scale() >= ProbeOverlay::MIN_PROBE_DISTANCE {
+            // only the probes in the window
+            let origin = config.probe_position_from_index(self.cascade, (0, 0));
+            let window_min = viewport.window_to_scene((0., 0.));
+            let window_max = viewport
+                .window_to_scene((viewport.window_size.0 as f32, viewport.window_size.1 as f32));
+            let index_range = |min: f32, max: f32, origin: f32, count: u32| {
+                let first = ((min - origin) / spacing).floor().max(0.) as u32;
+                let last = (((max - origin) / spacing).ceil().max(0.) as u32).min(count - 1);
+                first..=last
+            };
+
+            for y in index_range(window_min.1, window_max.1, origin.1, spatial.1) {
+                for x in index_range(window_min.0, window_max.0, origin.0, spatial.0) {
+                    painter.circle_filled(
+                        to_window(config.probe_position_from_index(self.cascade, (x, y))),
+                        1.5,
+                        egui::Color32::from_white_alpha(160),
+                    );
+                }
+            }
+        }
+
+        let probe = (
+            self.probe.0.min(spatial.0 - 1),
+            self.probe.1.min(spatial.1 - 1),
+        );
+        let center = config.probe_position_from_index(self.cascade, probe);
+        let window_center = to_window(center);
+        let offset = config.cascade_ray_offset(self.cascade);
+        let end = offset + config.cascade_ray_length(self.cascade);
+
+        let ring_stroke = egui::Stroke::new(1., egui::Color32::from_rgb(80, 200, 255));
+        painter.circle_stroke(window_center, offset * viewport.scale(), ring_stroke);
+        painter.circle_stroke(window_center, end * viewport.scale(), ring_stroke);
+
+        // same angles as the shader, every ray in the middle of its slice of the circle
+        let num_rays = config.cascade_angular_resolution(self.cascade);
+        let step = u32::div_ceil(num_rays, ProbeOverlay::MAX_DRAWN_RAYS);
+        let ray_stroke = egui::Stroke::new(1., egui::Color32::from_rgb(255, 210, 60));
+        for ray in (0..num_rays).step_by(step as usize) {
+            let angle = (ray as f32 + 0.5) * std::f32::consts::TAU / num_rays as f32;
+            let dir = (angle.cos(), angle.sin());
+            painter.line_segment(
+                [
+                    to_window((center.0 + dir.0 * offset, center.1 + dir.1 * offset)),
+                    to_window((center.0 + dir.0 * end, center.1 + dir.1 * end)),
+                ],
+                ray_stroke,
+            );
+        }
+
+        painter.circle_filled(window_center, 3., egui::Color32::from_rgb(255, 60, 60));
+    }
+}
@@ -0,0 +1,424 @@
+use super::config::RCConfig;
+use super::resources::RCResources;
+use crate::profiler::GpuProfiler;
+use egui_wgpu::wgpu;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// the probe whose bins are in a buffer
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct InspectedProbe {
+    cascade: u32,
+    probe: (u32, u32),
+    num_dirs: u32,
+    num_rays: u32,
+}
+
+struct InspectorResult {
+    probe: InspectedProbe,
+    /// the stored directions, after merging with the cascade above
+    merged: Vec<[f32; 4]>,
+    /// every ray of the probe's interval, without merging
+    traced: Vec<[f32; 4]>,
+}
+
+/// reads the direction bins of one probe back from the cascade buffers and plots them
+///
+/// the probe is traced again without merging by the `inspect_probe` entry point of
+/// the main shader, right after its cascade is written, the result shows up a frame or two later
+pub struct ProbeInspector {
+    pub enabled: bool,
+    result: Option<InspectorResult>,
+    hovered_bin: Option<(bool, usize)>,
+
+    uniform_buffer: wgpu::Buffer,
+    // None while disabled or when the probe has too many rays
+    bins_buffer: Option<wgpu::Buffer>,
+    readback_buffer: Option<wgpu::Buffer>,
+
+    // what bins_buffer and readback_buffer hold
+    written: Cell<Option<InspectedProbe>>,
+    read: Cell<Option<InspectedProbe>>,
+
+    mapped: Arc<AtomicBool>,
+    in_flight: Cell<bool>,
+    // the bins buffer got newer bins while the readback buffer was mapped
+    pending: Cell<bool>,
+    // a readback was recorded and has to be mapped once it's submitted
+    unmapped: Cell<bool>,
+}
+
+impl ProbeInspector {
+    const MAX_RAYS: u32 = 1 << 16;
+    const BIN_SIZE: u64 = 16;
+    const PLOT_SIZE: f32 = 180.;
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("rc inspect uniform buffer"),
+            size: 16,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        ProbeInspector {
+            enabled: false,
+            result: None,
+            hovered_bin: None,
+
+            uniform_buffer,
+            bins_buffer: None,
+            readback_buffer: None,
+
+            written: Cell::new(None),
+            read: Cell::new(None),
+
+            mapped: Arc::new(AtomicBool::new(false)),
+            in_flight: Cell::new(false),
+            pending: Cell::new(false),
+            unmapped: Cell::new(false),
+        }
+    }
+
+    fn num_bins(config: &RCConfig, cascade: u32) -> (u32, u32) {
+        let num_rays = config.cascade_angular_resolution(cascade);
+        let rays_per_dir = match cascade {
+            0 => config.c0_rays,
+            _ => config.angular_scaling,
+        };
+        (num_rays / rays_per_dir, num_rays)
+    }
+
+    /// makes room for the bins of a cascade's probes, call after the config or selection changes
+    pub fn update_buffers(&mut self, device: &wgpu::Device, config: &RCConfig, cascade: u32) {
+        let (num_dirs, num_rays) = ProbeInspector::num_bins(config, cascade);
+        if !self.enabled || num_rays > ProbeInspector::MAX_RAYS {
+            self.bins_buffer = None;
+            self.readback_buffer = None;
+        } else {
+            let size = (num_dirs + num_rays) as u64 * ProbeInspector::BIN_SIZE;
+            if self.bins_buffer.as_ref().is_some_and(|b| b.size() == size) {
+                return;
+            }
+            self.bins_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("rc inspect bins buffer"),
+                size,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }));
+            self.readback_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("rc inspect readback buffer"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+        // a readback of the old buffers never finishes
+        self.written.set(None);
+        self.in_flight.set(false);
+        self.pending.set(false);
+        self.mapped.store(false, Ordering::Release);
+    }
+
+    /// whether `inspect` has to run after the pass of `cascade`
+    pub fn inspects(&self, cascade: u32, selected_cascade: u32) -> bool {
+        self.bins_buffer.is_some() && cascade == selected_cascade
+    }
+
+    pub fn waiting(&self) -> bool {
+        self.in_flight.get()
+    }
+
+    fn start_readback(&self, encoder: &mut wgpu::CommandEncoder) {
+        let (Some(bins_buffer), Some(readback_buffer)) = (&self.bins_buffer, &self.readback_buffer)
+        else {
+            return;
+        };
+        encoder.copy_buffer_to_buffer(bins_buffer, 0, readback_buffer, 0, bins_buffer.size());
+        self.read.set(self.written.get());
+        self.in_flight.set(true);
+    }
+
+    fn map_readback(&self) {
+        let Some(readback_buffer) = &self.readback_buffer else {
+            return;
+        };
+        let mapped = self.mapped.clone();
+        readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if result.is_ok() {
+                    mapped.store(true, Ordering::Release);
+                }
+            });
+    }
+
+    /// reads the probe out of the cascade that was just written,
+    /// `temp_bind_group` has to bind that cascade's buffer as prev_cascade,
+    /// call `after_submit` once the encoder is submitted
+    #[allow(clippy::too_many_arguments)]
+    pub fn inspect(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        config: &RCConfig,
+        resources: &RCResources,
+        in_texture_bind_group: &wgpu::BindGroup,
+        temp_bind_group: &wgpu::BindGroup,
+        cascade: u32,
+        probe: (u32, u32),
+        profiler: &GpuProfiler,
+    ) {
+        let Some(bins_buffer) = &self.bins_buffer else {
+            return;
+        };
+        let (num_dirs, num_rays) = ProbeInspector::num_bins(config, cascade);
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&[probe.0, probe.1, 0, 0]),
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("rc inspect bind group"),
+            layout: &resources.inspect_bgl,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: bins_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("rc inspect pass"),
+                timestamp_writes: profiler.compute_pass_timestamps("rc inspect probe"),
+            });
+            compute_pass.set_pipeline(&resources.inspect_pipeline);
+            compute_pass.set_bind_group(
+                0,
+                &resources.uniform_bind_group,
+                &[resources.uniform_offset(cascade)],
+            );
+            compute_pass.set_bind_group(1, in_texture_bind_group, &[]);
+            compute_pass.set_bind_group(2, temp_bind_group, &[]);
+            compute_pass.set_bind_group(3, &bind_group, &[]);
+            compute_pass.dispatch_workgroups(u32::div_ceil(num_rays, 64), 1, 1);
+        }
+        self.written.set(Some(InspectedProbe {
+            cascade,
+            probe,
+            num_dirs,
+            num_rays,
+        }));
+
+        if self.in_flight.get() {
+            self.pending.set(true);
+        } else {
+            self.start_readback(encoder);
+            self.unmapped.set(true);
+        }
+    }
+
+    pub fn after_submit(&self) {
+        if self.unmapped.take() {
+            self.map_readback();
+        }
+    }
+
+    /// picks up a finished readback and starts the pending one
+    pub fn poll(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if !self.in_flight.get() {
+            return;
+        }
+        device.poll(wgpu::Maintain::Poll);
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return;
+        }
+        let Some(readback_buffer) = &self.readback_buffer else {
+            return;
+        };
+
+        if let Some(probe) = self.read.get() {
+            let data = readback_buffer.slice(..).get_mapped_range();
+            let bins: Vec<[f32; 4]> = bytemuck::cast_slice(&data).to_vec();
+            let (merged, traced) = bins.split_at(probe.num_dirs as usize);
+            self.result = Some(InspectorResult {
+                probe,
+                merged: merged.to_vec(),
+                traced: traced[..probe.num_rays as usize].to_vec(),
+            });
+        }
+        readback_buffer.unmap();
+        self.in_flight.set(false);
+
+        if self.pending.take() {
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("rc inspect readback encoder"),
+            });
+            self.start_readback(&mut encoder);
+            queue.submit(Some(encoder.finish()));
+            self.map_readback();
+        }
+    }
+
+    fn luminance(bin: &[f32; 4]) -> f32 {
+        0.2126 * bin[0] + 0.7152 * bin[1] + 0.0722 * bin[2]
+    }
+
+    /// a wedge per bin, as long as its luminance relative to `max_luminance`,
+    /// returns the hovered bin
+    fn polar_plot(ui: &mut egui::Ui, bins: &[[f32; 4]], max_luminance: f32) -> Option<usize> {
+        let (response, painter) = ui.allocate_painter(
+            egui::Vec2::splat(ProbeInspector::PLOT_SIZE),
+            egui::Sense::hover(),
+        );
+        let center = response.rect.center();
+        let max_radius = ProbeInspector::PLOT_SIZE * 0.5 - 2.;
+
+        painter.rect_filled(response.rect, 2., egui::Color32::from_gray(20));
+        painter.circle_stroke(
+            center,
+            max_radius,
+            egui::Stroke::new(1., egui::Color32::from_gray(60)),
+        );
+
+        // same angles as the shader, y points down in the scene and on screen
+        let num_bins = bins.len();
+        let segments = usize::div_ceil(64, num_bins.max(1));
+        let point =
+            |angle: f32, radius: f32| center + radius * egui::vec2(angle.cos(), angle.sin());
+
+        let mut mesh = egui::Mesh::default();
+        for (i, bin) in bins.iter().enumerate() {
+            let radius =
+                max_radius * (ProbeInspector::luminance(bin) / max_luminance).clamp(0., 1.);
+            let color: egui::Color32 =
+                egui::Rgba::from_rgb(bin[0].max(0.), bin[1].max(0.), bin[2].max(0.)).into();
+            for s in 0..segments {
+                let angle = |s: usize| {
+                    (i * segments + s) as f32 * std::f32::consts::TAU / (num_bins * segments) as f32
+                };
+                let first = mesh.vertices.len() as u32;
+                mesh.colored_vertex(center, color);
+                mesh.colored_vertex(point(angle(s), radius), color);
+                mesh.colored_vertex(point(angle(s + 1), radius), color);
+                mesh.add_triangle(first, first + 1, first + 2);
+            }
+        }
+        painter.add(mesh);
+
+        let pointer = response.hover_pos()?;
+        let offset = pointer - center;
+        if offset.length() > max_radius || num_bins == 0 {
+            return None;
+        }
+        let angle = offset.y.atan2(offset.x).rem_euclid(std::f32::consts::TAU);
+        let bin = (angle / std::f32::consts::TAU * num_bins as f32) as usize;
+        Some(bin.min(num_bins - 1))
+    }
+
+    /// returns whether the config changed
+    pub fn render_egui(&mut self, ui: &mut egui::Ui, config: &RCConfig, cascade: u32) -> bool {
+        let old_enabled = self.enabled;
+        ui.checkbox(&mut self.enabled, "Read back the probe");
+        if !self.enabled {
+            return self.enabled != old_enabled;
+        }
+
+        let (_, num_rays) = ProbeInspector::num_bins(config, cascade);
+        if num_rays > ProbeInspector::MAX_RAYS {
+            ui.colored_label(
+                egui::Color32::from_rgb(255, 0, 0),
+                format!(
+                    "{} rays, can't inspect more than {}",
+                    num_rays,
+                    ProbeInspector::MAX_RAYS
+                ),
+            );
+            return self.enabled != old_enabled;
+        }
+
+        match &self.result {
+            Some(result) => {
+                let max_luminance = result
+                    .merged
+                    .iter()
+                    .chain(&result.traced)
+                    .map(ProbeInspector::luminance)
+                    .fold(1e-6, f32::max);
+                let hits = result.traced.iter().filter(|bin| bin[3] >= 0.99).count();
+
+                ui.label(format!(
+                    "cascade {}, probe ({}, {}), {} of {} rays hit",
+                    result.probe.cascade,
+                    result.probe.probe.0,
+                    result.probe.probe.1,
+                    hits,
+                    result.probe.num_rays,
+                ));
+
+                let mut hovered = None;
+                ui.horizontal(|ui| {
+                    ui.vertical(|ui| {
+                        ui.label("Traced interval");
+                        if let Some(bin) =
+                            ProbeInspector::polar_plot(ui, &result.traced, max_luminance)
+                        {
+                            hovered = Some((false, bin));
+                        }
+                    });
+                    ui.vertical(|ui| {
+                        ui.label("Merged");
+                        if let Some(bin) =
+                            ProbeInspector::polar_plot(ui, &result.merged, max_luminance)
+                        {
+                            hovered = Some((true, bin));
+                        }
+                    });
+                });
+                // keeps the last hovered bin so the text doesn't flicker
+                if hovered.is_some() {
+                    self.hovered_bin = hovered;
+                }
+
+                let hovered_text = self.hovered_bin.and_then(|(merged, i)| {
+                    let bins = if merged {
+                        &result.merged
+                    } else {
+                        &result.traced
+                    };
+                    let bin = bins.get(i)?;
+                    let degrees = (i as f32 + 0.5) * 360. / bins.len() as f32;
+                    Some(format!(
+                        "{} {} at {:.1}°\n{:.4} {:.4} {:.4} {:.4}",
+                        if merged { "direction" } else { "ray" },
+                        i,
+                        degrees,
+                        bin[0],
+                        bin[1],
+                        bin[2],
+                        bin[3],
+                    ))
+                });
+                ui.monospace(hovered_text.unwrap_or_else(|| "hover a plot".to_string()));
+            }
+            None => {
+                ui.label("Waiting for the gpu...");
+            }
+        }
+        ui.colored_label(
+            egui::Color32::from_rgb(150, 150, 150),
+            "ctrl+click picks the probe closest to the cursor",
+        );
+
+        self.enabled != old_enabled
+    }
+}
@@ -1,61 +1,392 @@
-use super::config::RCConfig;
 use super::config::RawUniformData;
+use super::config::{CascadePrecision, CascadeStorage, RCConfig};
+use super::storage::{self, Access};
+use crate::gi::SRGB_WGSL;
+use crate::shaders::{self, shader_file, ComputePipeline, ShaderFile};
 use egui_wgpu::wgpu;
 
+/// the two ping-ponged cascades, see `CascadeStorage`
+pub enum Cascades {
+    Buffers([wgpu::Buffer; 2]),
+    Textures([wgpu::Texture; 2], [wgpu::TextureView; 2]),
+}
+
+impl Cascades {
+    fn binding_resource(&self, index: usize) -> wgpu::BindingResource<'_> {
+        match self {
+            Cascades::Buffers(buffers) => buffers[index].as_entire_binding(),
+            Cascades::Textures(_, views) => wgpu::BindingResource::TextureView(&views[index]),
+        }
+    }
+}
+
+/// everything the rc renderer keeps between frames, `update` only rebuilds what a config change touches
 pub struct RCResources {
-    pub uniform_buffer: wgpu::Buffer,
+    // what the cascade layouts and pipelines were built for
+    storage: CascadeStorage,
+    precision: CascadePrecision,
 
-    pub cascade_buffers: [wgpu::Buffer; 2],
+    nearest_sampler: wgpu::Sampler,
+    linear_sampler: wgpu::Sampler,
 
-    pub sdf_view: wgpu::TextureView,
+    uniform_bgl: wgpu::BindGroupLayout,
+    temp_bgl: wgpu::BindGroupLayout,
 
-    // uniform buffer, samplers, sdf texture
+    /// a RawUniformData per cascade, `uniform_stride` apart, may fit more cascades than are used
+    pub uniform_buffer: wgpu::Buffer,
+    uniform_stride: u64,
+
+    cascades: Cascades,
+    // elements the cascades fit, at least the max cascade size
+    cascade_capacity: u32,
+
+    // uniform buffer, samplers
     pub uniform_bind_group: wgpu::BindGroup,
 
+    // scene, sdf
     pub in_texture_bgl: wgpu::BindGroupLayout,
     // ith bind group writes to ith buffer
     pub temp_bind_groups: [wgpu::BindGroup; 2],
 
     pub final_bgl: wgpu::BindGroupLayout,
+    // inspected probe uniform, output bins
+    pub inspect_bgl: wgpu::BindGroupLayout,
 
-    pub main_pipeline: wgpu::ComputePipeline,
-    pub final_pipeline: wgpu::ComputePipeline,
+    pub main_pipeline: ComputePipeline,
+    pub final_pipeline: ComputePipeline,
+    /// the `inspect_probe` entry point of the main shader
+    pub inspect_pipeline: ComputePipeline,
 }
 
 impl RCResources {
-    pub const SDF_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
-    // cascade buffers store vec2<u32>s
-    pub const CASCADE_BUFFER_ELEM_SIZE: u32 = 8;
-
-    fn create_sdf_texture(device: &wgpu::Device, size: (u32, u32)) -> wgpu::Texture {
-        device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("rc sdf texture"),
-            size: wgpu::Extent3d {
-                width: size.0,
-                height: size.1,
-                depth_or_array_layers: 1,
+    const MAIN_SHADER: ShaderFile = shader_file!("src/gi/radiance_cascades/shader.wgsl");
+    const FINAL_SHADER: ShaderFile = shader_file!("src/gi/radiance_cascades/final_shader.wgsl");
+
+    fn cascade_texture_format(precision: CascadePrecision) -> wgpu::TextureFormat {
+        match precision {
+            CascadePrecision::Half => wgpu::TextureFormat::Rgba16Float,
+            CascadePrecision::Full => wgpu::TextureFormat::Rgba32Float,
+        }
+    }
+
+    /// the most elements a cascade can have on a device
+    pub fn max_cascade_size(limits: &wgpu::Limits, config: &RCConfig) -> u32 {
+        let max_elems = match config.storage {
+            CascadeStorage::Buffer => {
+                u64::min(
+                    limits.max_buffer_size,
+                    limits.max_storage_buffer_binding_size as u64,
+                ) / config.precision.elem_size() as u64
+            }
+            CascadeStorage::Texture => {
+                let max_dimension = limits.max_texture_dimension_2d as u64;
+                max_dimension * max_dimension * limits.max_texture_array_layers as u64
+            }
+        };
+        // the shaders index with u32s, and get_cascade_size saturates to u32::MAX
+        max_elems.min(u32::MAX as u64 - 1) as u32
+    }
+
+    /// rows of the max width spread over as few layers as possible
+    fn cascade_texture_size(
+        limits: &wgpu::Limits,
+        precision: CascadePrecision,
+        num_elems: u32,
+    ) -> wgpu::Extent3d {
+        let max_dimension = limits.max_texture_dimension_2d;
+        // rows are copied to the debug view back to back, which needs aligned rows
+        let row_alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT / precision.elem_size();
+        let width = num_elems
+            .next_multiple_of(row_alignment)
+            .clamp(row_alignment, max_dimension);
+        let rows = u32::div_ceil(num_elems, width);
+        // the gl backend takes single layer textures for D2 ones, no matter the view
+        let layers = u32::div_ceil(rows, max_dimension).max(2);
+        wgpu::Extent3d {
+            width,
+            height: u32::div_ceil(rows, layers),
+            depth_or_array_layers: layers,
+        }
+    }
+
+    fn create_cascades(device: &wgpu::Device, config: &RCConfig, num_elems: u32) -> Cascades {
+        match config.storage {
+            CascadeStorage::Buffer => Cascades::Buffers(core::array::from_fn(|_| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("an rc cascade buffer"),
+                    size: num_elems as u64 * config.precision.elem_size() as u64,
+                    // copied from by the debug view
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                })
+            })),
+            CascadeStorage::Texture => {
+                let textures: [wgpu::Texture; 2] = core::array::from_fn(|_| {
+                    device.create_texture(&wgpu::TextureDescriptor {
+                        label: Some("an rc cascade texture"),
+                        size: RCResources::cascade_texture_size(
+                            &device.limits(),
+                            config.precision,
+                            num_elems,
+                        ),
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: RCResources::cascade_texture_format(config.precision),
+                        // copied from by the debug view
+                        usage: wgpu::TextureUsages::TEXTURE_BINDING
+                            | wgpu::TextureUsages::STORAGE_BINDING
+                            | wgpu::TextureUsages::COPY_SRC,
+                        view_formats: &[],
+                    })
+                });
+                let views = core::array::from_fn(|i| {
+                    textures[i].create_view(&wgpu::TextureViewDescriptor {
+                        dimension: Some(wgpu::TextureViewDimension::D2Array),
+                        ..Default::default()
+                    })
+                });
+                Cascades::Textures(textures, views)
+            }
+        }
+    }
+
+    fn cascade_layout_entry(
+        config: &RCConfig,
+        binding: u32,
+        access: Access,
+    ) -> wgpu::BindGroupLayoutEntry {
+        let ty = match (config.storage, access) {
+            (CascadeStorage::Buffer, access) => wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage {
+                    read_only: access == Access::Read,
+                },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            (CascadeStorage::Texture, Access::Read) => wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2Array,
+                multisampled: false,
             },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: RCResources::SDF_FORMAT,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
-            view_formats: &[],
+            (CascadeStorage::Texture, Access::Write) => wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: RCResources::cascade_texture_format(config.precision),
+                view_dimension: wgpu::TextureViewDimension::D2Array,
+            },
+        };
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty,
+            count: None,
+        }
+    }
+
+    /// the ith cascade is written by the ith temp bind group
+    pub fn cascades(&self) -> &Cascades {
+        &self.cascades
+    }
+
+    const UNIFORM_SIZE: u64 = std::mem::size_of::<RawUniformData>() as u64;
+
+    // the uniform bind groups only see one cascade's uniforms
+    fn uniform_binding(uniform_buffer: &wgpu::Buffer) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer: uniform_buffer,
+            offset: 0,
+            size: wgpu::BufferSize::new(RCResources::UNIFORM_SIZE),
         })
     }
 
-    fn create_cascade_buffers(device: &wgpu::Device, num_elems: u32) -> [wgpu::Buffer; 2] {
-        core::array::from_fn(|_| {
-            device.create_buffer(&wgpu::BufferDescriptor {
-                label: Some("an rc cascade buffer"),
-                size: (num_elems * RCResources::CASCADE_BUFFER_ELEM_SIZE) as wgpu::BufferAddress,
-                usage: wgpu::BufferUsages::STORAGE,
-                mapped_at_creation: false,
+    fn create_uniform_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        nearest_sampler: &wgpu::Sampler,
+        linear_sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("rc uniform bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: RCResources::uniform_binding(uniform_buffer),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(nearest_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(linear_sampler),
+                },
+            ],
+        })
+    }
+
+    fn create_temp_bind_groups(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        cascades: &Cascades,
+    ) -> [wgpu::BindGroup; 2] {
+        core::array::from_fn(|i| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("an rc temp texture bind group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: cascades.binding_resource(1 - i),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: cascades.binding_resource(i),
+                    },
+                ],
             })
         })
     }
 
-    pub fn new(device: &wgpu::Device, window_size: (u32, u32), config: RCConfig) -> Self {
+    fn create_uniform_buffer(
+        device: &wgpu::Device,
+        uniform_stride: u64,
+        num_cascades: u32,
+    ) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("rc uniform bufer"),
+            size: uniform_stride * num_cascades as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_temp_bgl(device: &wgpu::Device, config: &RCConfig) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("rc temp bgl"),
+            entries: &[
+                RCResources::cascade_layout_entry(config, 0, Access::Read),
+                RCResources::cascade_layout_entry(config, 1, Access::Write),
+            ],
+        })
+    }
+
+    fn create_final_bgl(device: &wgpu::Device, config: &RCConfig) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("rc final bgl"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                RCResources::cascade_layout_entry(config, 1, Access::Read),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba16Float,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    /// the main, final and inspect pipelines, their prefixes declare the cascades
+    fn create_pipelines(
+        device: &wgpu::Device,
+        config: &RCConfig,
+        uniform_bgl: &wgpu::BindGroupLayout,
+        in_texture_bgl: &wgpu::BindGroupLayout,
+        temp_bgl: &wgpu::BindGroupLayout,
+        final_bgl: &wgpu::BindGroupLayout,
+        inspect_bgl: &wgpu::BindGroupLayout,
+    ) -> (ComputePipeline, ComputePipeline, ComputePipeline) {
+        let main_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("rc main pipeline layout"),
+            bind_group_layouts: &[uniform_bgl, in_texture_bgl, temp_bgl],
+            push_constant_ranges: &[],
+        });
+
+        let main_prefix = [
+            SRGB_WGSL.to_owned(),
+            storage::cascade_binding(
+                config.storage,
+                config.precision,
+                2,
+                0,
+                "prev_cascade",
+                Access::Read,
+            ),
+            storage::cascade_binding(
+                config.storage,
+                config.precision,
+                2,
+                1,
+                "out_cascade",
+                Access::Write,
+            ),
+        ]
+        .concat();
+        let main_pipeline = ComputePipeline::with_prefix(
+            device,
+            "rc main pipeline",
+            RCResources::MAIN_SHADER,
+            main_prefix.clone(),
+            main_pipeline_layout,
+        );
+
+        let final_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("rc final pipeline layout"),
+                bind_group_layouts: &[final_bgl],
+                push_constant_ranges: &[],
+            });
+
+        let final_pipeline = ComputePipeline::with_prefix(
+            device,
+            "rc final pipeline",
+            RCResources::FINAL_SHADER,
+            storage::cascade_binding(
+                config.storage,
+                config.precision,
+                0,
+                1,
+                "cascade_0",
+                Access::Read,
+            ),
+            final_pipeline_layout,
+        );
+
+        let inspect_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("rc inspect pipeline layout"),
+                bind_group_layouts: &[uniform_bgl, in_texture_bgl, temp_bgl, inspect_bgl],
+                push_constant_ranges: &[],
+            });
+
+        let inspect_pipeline = ComputePipeline::with_entry_point(
+            device,
+            "rc inspect pipeline",
+            RCResources::MAIN_SHADER,
+            main_prefix,
+            "inspect_probe",
+            inspect_pipeline_layout,
+        );
+
+        (main_pipeline, final_pipeline, inspect_pipeline)
+    }
+
+    pub fn new(device: &wgpu::Device, texture_size: (u32, u32), config: RCConfig) -> Self {
         let nearest_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("rc nearest sampler"),
             mag_filter: wgpu::FilterMode::Nearest,
@@ -69,18 +400,15 @@ impl RCResources {
             ..Default::default()
         });
 
-        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("rc uniform bufer"),
-            size: std::mem::size_of::<RawUniformData>() as u64,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-
-        let cascade_buffers =
-            RCResources::create_cascade_buffers(&device, config.get_max_cascade_size(window_size));
+        let uniform_stride = u64::next_multiple_of(
+            RCResources::UNIFORM_SIZE,
+            device.limits().min_uniform_buffer_offset_alignment as u64,
+        );
+        let uniform_buffer =
+            RCResources::create_uniform_buffer(device, uniform_stride, config.num_cascades);
 
-        let sdf_texture = RCResources::create_sdf_texture(device, window_size);
-        let sdf_view = sdf_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let cascade_capacity = config.get_max_cascade_size(texture_size);
+        let cascades = RCResources::create_cascades(device, &config, cascade_capacity);
 
         let uniform_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("rc uniform bind group layout"),
@@ -90,7 +418,8 @@ impl RCResources {
                     visibility: wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
+                        // picks the cascade, see `uniform_offset`
+                        has_dynamic_offset: true,
                         min_binding_size: None,
                     },
                     count: None,
@@ -107,105 +436,49 @@ impl RCResources {
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 3,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    count: None,
-                },
             ],
         });
 
-        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("rc uniform bind group"),
-            layout: &uniform_bgl,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: uniform_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&nearest_sampler),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::Sampler(&linear_sampler),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 3,
-                    resource: wgpu::BindingResource::TextureView(&sdf_view),
-                },
-            ],
-        });
+        let uniform_bind_group = RCResources::create_uniform_bind_group(
+            device,
+            &uniform_bgl,
+            &uniform_buffer,
+            &nearest_sampler,
+            &linear_sampler,
+        );
 
         let in_texture_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("rc in texture bind group layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    multisampled: false,
-                },
-                count: None,
-            }],
-        });
-
-        let temp_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("rc temp bgl"),
             entries: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
                     visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
                     },
                     count: None,
                 },
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
                     visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: false },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
                     },
                     count: None,
                 },
             ],
         });
 
-        let temp_bind_groups: [wgpu::BindGroup; 2] = core::array::from_fn(|i| {
-            device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("an rc temp texture bind group"),
-                layout: &temp_bgl,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::Buffer(
-                            cascade_buffers[1 - i].as_entire_buffer_binding(),
-                        ),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Buffer(
-                            cascade_buffers[i].as_entire_buffer_binding(),
-                        ),
-                    },
-                ],
-            })
-        });
+        let temp_bgl = RCResources::create_temp_bgl(device, &config);
+        let temp_bind_groups = RCResources::create_temp_bind_groups(device, &temp_bgl, &cascades);
+        let final_bgl = RCResources::create_final_bgl(device, &config);
 
-        let final_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("rc final bgl"),
+        let inspect_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("rc inspect bgl"),
             entries: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
@@ -221,87 +494,158 @@ impl RCResources {
                     binding: 1,
                     visibility: wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
                         has_dynamic_offset: false,
                         min_binding_size: None,
                     },
                     count: None,
                 },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::StorageTexture {
-                        access: wgpu::StorageTextureAccess::WriteOnly,
-                        format: wgpu::TextureFormat::Rgba16Float,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                    },
-                    count: None,
-                },
             ],
         });
 
-        let shader_module = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
-        let final_shader_module =
-            device.create_shader_module(wgpu::include_wgsl!("final_shader.wgsl"));
-
-        let main_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("rc main pipeline layout"),
-            bind_group_layouts: &[&uniform_bgl, &in_texture_bgl, &temp_bgl],
-            push_constant_ranges: &[],
-        });
+        let (main_pipeline, final_pipeline, inspect_pipeline) = RCResources::create_pipelines(
+            device,
+            &config,
+            &uniform_bgl,
+            &in_texture_bgl,
+            &temp_bgl,
+            &final_bgl,
+            &inspect_bgl,
+        );
 
-        let main_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("rc main pipeline"),
-            layout: Some(&main_pipeline_layout),
-            module: &shader_module,
-            entry_point: "main",
-            compilation_options: Default::default(),
-        });
+        RCResources {
+            storage: config.storage,
+            precision: config.precision,
 
-        let final_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("rc final pipeline layout"),
-                bind_group_layouts: &[&final_bgl],
-                push_constant_ranges: &[],
-            });
+            nearest_sampler,
+            linear_sampler,
 
-        let final_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("rc final pipeline"),
-            layout: Some(&final_pipeline_layout),
-            module: &final_shader_module,
-            entry_point: "main",
-            compilation_options: Default::default(),
-        });
+            uniform_bgl,
+            temp_bgl,
 
-        RCResources {
             uniform_buffer,
+            uniform_stride,
 
-            cascade_buffers,
-
-            sdf_view,
+            cascades,
+            cascade_capacity,
 
             uniform_bind_group,
             in_texture_bgl,
             temp_bind_groups,
             final_bgl,
+            inspect_bgl,
 
             main_pipeline,
             final_pipeline,
+            inspect_pipeline,
         }
     }
 
+    /// rebuilds what a config change needs, the uniforms are written every frame anyway
+    pub fn update(&mut self, device: &wgpu::Device, texture_size: (u32, u32), config: RCConfig) {
+        // the cascade bindings change type
+        let layout_changed = config.storage != self.storage || config.precision != self.precision;
+        if layout_changed {
+            self.storage = config.storage;
+            self.precision = config.precision;
+            self.temp_bgl = RCResources::create_temp_bgl(device, &config);
+            self.final_bgl = RCResources::create_final_bgl(device, &config);
+            (
+                self.main_pipeline,
+                self.final_pipeline,
+                self.inspect_pipeline,
+            ) = RCResources::create_pipelines(
+                device,
+                &config,
+                &self.uniform_bgl,
+                &self.in_texture_bgl,
+                &self.temp_bgl,
+                &self.final_bgl,
+                &self.inspect_bgl,
+            );
+        }
+
+        if config.num_cascades as u64 * self.uniform_stride > self.uniform_buffer.size() {
+            self.uniform_buffer = RCResources::create_uniform_buffer(
+                device,
+                self.uniform_stride,
+                config.num_cascades,
+            );
+            self.uniform_bind_group = RCResources::create_uniform_bind_group(
+                device,
+                &self.uniform_bgl,
+                &self.uniform_buffer,
+                &self.nearest_sampler,
+                &self.linear_sampler,
+            );
+        }
+
+        let max_cascade_size = config.get_max_cascade_size(texture_size);
+        if layout_changed || max_cascade_size > self.cascade_capacity {
+            self.cascade_capacity = max_cascade_size;
+            self.cascades = RCResources::create_cascades(device, &config, max_cascade_size);
+            self.temp_bind_groups =
+                RCResources::create_temp_bind_groups(device, &self.temp_bgl, &self.cascades);
+        }
+    }
+
+    /// recreates the cascades, the only resources that depend on the texture size
+    pub fn resize(&mut self, device: &wgpu::Device, texture_size: (u32, u32), config: RCConfig) {
+        // sized to fit again, so that shrinking the window frees memory
+        self.cascade_capacity = config.get_max_cascade_size(texture_size);
+        self.cascades = RCResources::create_cascades(device, &config, self.cascade_capacity);
+        self.temp_bind_groups =
+            RCResources::create_temp_bind_groups(device, &self.temp_bgl, &self.cascades);
+    }
+
+    pub fn reload_shaders(&mut self, device: &wgpu::Device) -> Result<(), String> {
+        shaders::join_errors([
+            self.main_pipeline.reload(device),
+            self.final_pipeline.reload(device),
+            self.inspect_pipeline.reload(device),
+        ])
+    }
+
+    /// writes the uniforms of every cascade, `cascade_mask` as in the shader
+    pub fn write_uniforms(&self, queue: &wgpu::Queue, config: RCConfig, cascade_mask: u32) {
+        let mut data = vec![0; self.uniform_buffer.size() as usize];
+        for cascade in 0..config.num_cascades {
+            let uniform_data = RawUniformData {
+                cur_cascade: cascade,
+                cascade_mask,
+                ..RawUniformData::from(config)
+            };
+            let offset = self.uniform_offset(cascade) as usize;
+            data[offset..offset + RCResources::UNIFORM_SIZE as usize]
+                .copy_from_slice(bytemuck::bytes_of(&uniform_data));
+        }
+        queue.write_buffer(&self.uniform_buffer, 0, &data);
+    }
+
+    /// dynamic offset of a cascade's uniforms in the uniform bind group
+    pub fn uniform_offset(&self, cascade: u32) -> u32 {
+        (cascade as u64 * self.uniform_stride) as u32
+    }
+
     pub fn create_texture_bind_group(
         &self,
         device: &wgpu::Device,
         in_texture_view: &wgpu::TextureView,
+        sdf_view: &wgpu::TextureView,
     ) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("rc textures bind group"),
             layout: &self.in_texture_bgl,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::TextureView(in_texture_view),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(in_texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(sdf_view),
+                },
+            ],
         })
     }
 
@@ -315,15 +659,14 @@ impl RCResources {
             label: Some("rc final bind group"),
             layout: &self.final_bgl,
             entries: &[
+                // cascade 0's uniforms
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: self.uniform_buffer.as_entire_binding(),
+                    resource: RCResources::uniform_binding(&self.uniform_buffer),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Buffer(
-                        self.cascade_buffers[temp_index].as_entire_buffer_binding(),
-                    ),
+                    resource: self.cascades.binding_resource(temp_index),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
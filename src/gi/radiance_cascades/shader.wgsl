@@ -15,6 +15,8 @@ struct uniform_data {
 
     num_cascades: u32,
     cur_cascade: u32,
+    // bit i set if cascade i is shown, the intervals of the others are transparent
+    cascade_mask: u32,
 }
 
 @group(0) @binding(0)
@@ -23,29 +25,16 @@ var<uniform> uniforms: uniform_data;
 var nearest_sampler: sampler;
 @group(0) @binding(2)
 var linear_sampler: sampler;
-@group(0) @binding(3)
-var sdf_texture: texture_2d<f32>;
 
 @group(1) @binding(0)
 var in_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var sdf_texture: texture_2d<f32>;
 
 
-@group(2) @binding(0)
-var<storage, read> prev_cascade: array<vec2u>;
-@group(2) @binding(1)
-var<storage, read_write> out_cascade: array<vec2u>;
-
-// TODO: figure out if its possible to make generic read/write functions for this
-// the problem is that arguments are immutable and immutable arrays can only be indexed with constants
-fn read_prev_cascade(pos: u32) -> vec4f {
-    let packed = prev_cascade[pos];
-    return vec4f(unpack2x16float(packed.x), unpack2x16float(packed.y));
-}
-
-fn store_to_out_cascade(pos: u32, value: vec4f) {
-    let packed = vec2u(pack2x16float(value.rg), pack2x16float(value.ba));
-    out_cascade[pos] = packed;
-}
+// group 2 holds prev_cascade and out_cascade, they depend on the storage and are
+// declared in the prefix along with read_prev_cascade and store_to_out_cascade, see storage.rs
+// the prefix also has srgb_to_linear, see gi/srgb.wgsl
 
 fn out_of_bounds(pos: vec2f, dims: vec2u) -> bool {
     return (pos.x < 0. || pos.y < 0. || pos.x >= f32(dims.x) || pos.y >= f32(dims.y));
@@ -67,7 +56,7 @@ fn march_ray(start_pos: vec2f, dir: vec2f, maxlen: f32) -> vec4f {
         if dist < 1 {
             let color = textureSampleLevel(in_texture, nearest_sampler, pos * texel, 0.);
             if color.a > 0.99 {
-                return color;
+                return vec4f(srgb_to_linear(color.rgb), color.a);
             }
         }
 
@@ -84,8 +73,13 @@ fn march_ray(start_pos: vec2f, dir: vec2f, maxlen: f32) -> vec4f {
 
 @compute
 @workgroup_size(128)
-fn main(@builtin(global_invocation_id) id3d: vec3u) {
-    let id = id3d.x;
+fn main(@builtin(global_invocation_id) id3d: vec3u, @builtin(num_workgroups) num_groups: vec3u) {
+    // big cascades need more than one row of workgroups
+    let id = id3d.y * num_groups.x * 128u + id3d.x;
+    let spatial_resolution = cascade_spatial_resolution(uniforms.cur_cascade);
+    if id >= cascade_num_dirs(uniforms.cur_cascade) * spatial_resolution.x * spatial_resolution.y {
+        return;
+    }
     var result: vec4f;
 
     if uniforms.ringing_fix == 0 {
@@ -103,6 +97,11 @@ fn cascade_angular_resolution(cascade_index: u32) -> u32 {
     return uniforms.c0_rays * mult;
 }
 
+// directions stored per probe, a stored direction merges the rays of a c0 probe or angular_scaling rays
+fn cascade_num_dirs(cascade_index: u32) -> u32 {
+    return cascade_angular_resolution(cascade_index) / select(uniforms.c0_rays, uniforms.angular_scaling, cascade_index != 0);
+}
+
 fn cascade_probe_spacing(cascade_index: u32) -> f32 {
     let mult = pow(f32(uniforms.spatial_scaling), f32(cascade_index));
     return uniforms.c0_spacing * mult;
@@ -178,7 +177,7 @@ fn rc(id: u32) -> vec4f {
         let dir = vec2f(cos(angle), sin(angle));
 
         let pos = probe_position(cascade, id) + dir * cascade_ray_offset(cascade);
-        let ray_color = march_ray(pos, dir, cascade_ray_length(cascade));
+        let ray_color = shown_interval(cascade, march_ray(pos, dir, cascade_ray_length(cascade)));
         let ray_result = merge(id, ray_color, get_ray_index(cascade, id) * num_rays + i);
 
         result += ray_result;
@@ -268,10 +267,7 @@ fn rc_bilinear(id: u32) -> vec4f {
                 next_color = read_prev_cascade(merge_buffer_index);
             }
 
-            let ray_start = probe_pos + ray_dir * cascade_ray_offset(cascade);
-            let ray_end = merge_probe_pos + ray_dir * cascade_ray_offset(cascade + 1);
-
-            let ray_color = march_ray(ray_start, normalize(ray_end - ray_start), distance(ray_end, ray_start));
+            let ray_color = shown_interval(cascade, bilinear_interval(cascade, probe_pos, merge_probe_pos, ray_dir));
             let probe_result = select(next_color, ray_color, ray_color.a >= 0.99);
 
             result += weights[j] * probe_result;
@@ -283,6 +279,17 @@ fn rc_bilinear(id: u32) -> vec4f {
     return result;
 }
 
+// the interval from a probe's ray to the same ray of a probe in the next cascade
+fn bilinear_interval(cascade: u32, probe_pos: vec2f, merge_probe_pos: vec2f, ray_dir: vec2f) -> vec4f {
+    let ray_start = probe_pos + ray_dir * cascade_ray_offset(cascade);
+    let ray_end = merge_probe_pos + ray_dir * cascade_ray_offset(cascade + 1);
+    return march_ray(ray_start, normalize(ray_end - ray_start), distance(ray_end, ray_start));
+}
+
+fn shown_interval(cascade_index: u32, ray_color: vec4f) -> vec4f {
+    return select(vec4f(0.), ray_color, ((uniforms.cascade_mask >> cascade_index) & 1u) != 0u);
+}
+
 fn bilinear_weights(pos: vec2f) -> array<f32, 4> {
     return array(
         (1. - pos.x) * (1. - pos.y),
@@ -291,3 +298,54 @@ fn bilinear_weights(pos: vec2f) -> array<f32, 4> {
         pos.x * pos.y,
     );
 }
+
+// probe inspector, reads one probe of the cascade in prev_cascade back
+@group(3) @binding(0)
+var<uniform> inspected_probe: vec2u;
+// the stored directions first, then every traced ray without merging
+@group(3) @binding(1)
+var<storage, read_write> inspected_bins: array<vec4f>;
+
+@compute
+@workgroup_size(64)
+fn inspect_probe(@builtin(global_invocation_id) id3d: vec3u) {
+    let id = id3d.x;
+    let cascade = uniforms.cur_cascade;
+    let spatial_resolution = cascade_spatial_resolution(cascade);
+
+    let num_rays = cascade_angular_resolution(cascade);
+    let num_dirs = cascade_num_dirs(cascade);
+
+    if id < num_dirs {
+        let probe_id = inspected_probe.y * spatial_resolution.x + inspected_probe.x;
+        inspected_bins[id] = read_prev_cascade(id * spatial_resolution.x * spatial_resolution.y + probe_id);
+    }
+    if id < num_rays {
+        let angle = ray_angle_from_index(cascade, f32(id) + 0.5);
+        let dir = vec2f(cos(angle), sin(angle));
+        let probe_pos = probe_position_from_index(cascade, inspected_probe);
+
+        if uniforms.ringing_fix == 1 {
+            // the four intervals rc_bilinear traces, weighted the same way
+            let prev_probe_index = probe_index_from_position(cascade + 1, probe_pos);
+            let prev_spatial = cascade_spatial_resolution(cascade + 1);
+            let d = probe_pos - probe_position_from_index(cascade + 1, prev_probe_index);
+            var weights = bilinear_weights(d / cascade_probe_spacing(cascade + 1));
+
+            var result = vec4f(0.);
+            for (var j = 0u; j < 4u; j += 1u) {
+                if weights[j] < 0.01 {
+                    continue;
+                }
+                let offset = vec2u(j & 1, j >> 1);
+                let merge_probe_index = clamp(prev_probe_index + offset, vec2u(0), prev_spatial - 1);
+                let merge_probe_pos = probe_position_from_index(cascade + 1, merge_probe_index);
+                result += weights[j] * bilinear_interval(cascade, probe_pos, merge_probe_pos, dir);
+            }
+            inspected_bins[num_dirs + id] = result;
+        } else {
+            let pos = probe_pos + dir * cascade_ray_offset(cascade);
+            inspected_bins[num_dirs + id] = march_ray(pos, dir, cascade_ray_length(cascade));
+        }
+    }
+}
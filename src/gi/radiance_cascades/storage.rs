@@ -0,0 +1,103 @@
+use super::config::{CascadePrecision, CascadeStorage};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// wgsl declaring a cascade binding and `read_<name>(pos) -> vec4f` or
+/// `store_to_<name>(pos, value)`, prepended to the rc shaders
+///
+/// `pos` is the index in the flat layout, textures hold that row by row and layer by layer
+pub fn cascade_binding(
+    storage: CascadeStorage,
+    precision: CascadePrecision,
+    group: u32,
+    binding: u32,
+    name: &str,
+    access: Access,
+) -> String {
+    let declaration = match (storage, precision, access) {
+        (CascadeStorage::Buffer, CascadePrecision::Half, Access::Read) => {
+            "var<storage, read> NAME: array<vec2u>;"
+        }
+        (CascadeStorage::Buffer, CascadePrecision::Half, Access::Write) => {
+            "var<storage, read_write> NAME: array<vec2u>;"
+        }
+        (CascadeStorage::Buffer, CascadePrecision::Full, Access::Read) => {
+            "var<storage, read> NAME: array<vec4f>;"
+        }
+        (CascadeStorage::Buffer, CascadePrecision::Full, Access::Write) => {
+            "var<storage, read_write> NAME: array<vec4f>;"
+        }
+        (CascadeStorage::Texture, _, Access::Read) => "var NAME: texture_2d_array<f32>;",
+        (CascadeStorage::Texture, CascadePrecision::Half, Access::Write) => {
+            "var NAME: texture_storage_2d_array<rgba16float, write>;"
+        }
+        (CascadeStorage::Texture, CascadePrecision::Full, Access::Write) => {
+            "var NAME: texture_storage_2d_array<rgba32float, write>;"
+        }
+    };
+
+    let function = match (storage, precision, access) {
+        // f16 values are packed in two u32s
+        (CascadeStorage::Buffer, CascadePrecision::Half, Access::Read) => {
+            "
+fn read_NAME(pos: u32) -> vec4f {
+    let packed_value = NAME[pos];
+    return vec4f(unpack2x16float(packed_value.x), unpack2x16float(packed_value.y));
+}"
+        }
+        (CascadeStorage::Buffer, CascadePrecision::Half, Access::Write) => {
+            "
+fn store_to_NAME(pos: u32, value: vec4f) {
+    NAME[pos] = vec2u(pack2x16float(value.rg), pack2x16float(value.ba));
+}"
+        }
+        (CascadeStorage::Buffer, CascadePrecision::Full, Access::Read) => {
+            "
+fn read_NAME(pos: u32) -> vec4f {
+    return NAME[pos];
+}"
+        }
+        (CascadeStorage::Buffer, CascadePrecision::Full, Access::Write) => {
+            "
+fn store_to_NAME(pos: u32, value: vec4f) {
+    NAME[pos] = value;
+}"
+        }
+        (CascadeStorage::Texture, _, Access::Read) => {
+            "
+fn read_NAME(pos: u32) -> vec4f {
+    let texel = NAME_texel(pos);
+    return textureLoad(NAME, texel.xy, texel.z, 0);
+}"
+        }
+        (CascadeStorage::Texture, _, Access::Write) => {
+            "
+fn store_to_NAME(pos: u32, value: vec4f) {
+    let texel = NAME_texel(pos);
+    textureStore(NAME, texel.xy, texel.z, value);
+}"
+        }
+    };
+
+    let texel_function = match storage {
+        CascadeStorage::Buffer => "",
+        CascadeStorage::Texture => {
+            "
+fn NAME_texel(pos: u32) -> vec3u {
+    let size = textureDimensions(NAME);
+    let layer_size = size.x * size.y;
+    return vec3u(pos % size.x, (pos % layer_size) / size.x, pos / layer_size);
+}"
+        }
+    };
+
+    format!(
+        "@group({}) @binding({})\n{}\n{}\n{}\n",
+        group, binding, declaration, texel_function, function
+    )
+    .replace("NAME", name)
+}
@@ -1,5 +1,9 @@
+use super::distance_field::DistanceField;
 use super::GIRenderer;
-use crate::jfa::JFA;
+use super::RendererSession;
+use super::SRGB_WGSL;
+use crate::profiler::GpuProfiler;
+use crate::shaders::{shader_file, ComputePipeline, ShaderFile};
 use egui_wgpu::wgpu;
 
 #[repr(C)]
@@ -8,55 +12,57 @@ struct RawUniformData {
     ray_count: u32,
 }
 
-struct RaymarcherConfig {
-    ray_count: u32,
+#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct RaymarcherConfig {
+    pub ray_count: u32,
+}
+
+impl Default for RaymarcherConfig {
+    fn default() -> Self {
+        RaymarcherConfig { ray_count: 64 }
+    }
 }
 
 pub struct Raymarcher {
     pub label: String,
 
     config: RaymarcherConfig,
-    window_size: (u32, u32),
+    texture_size: (u32, u32),
 
     uniform_buffer: wgpu::Buffer,
-    sdf_texture: wgpu::Texture,
-    sdf_view: wgpu::TextureView,
-
-    jfa: JFA,
 
     uniform_bind_group: wgpu::BindGroup,
 
     // sdf, in, out
     textures_bgl: wgpu::BindGroupLayout,
 
-    pipeline: wgpu::ComputePipeline,
+    pipeline: ComputePipeline,
 }
 
 impl Raymarcher {
-    const SDF_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
-
-    fn create_sdf_texture(device: &wgpu::Device, size: (u32, u32)) -> wgpu::Texture {
-        device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("raymarcher sdf texture"),
-            size: wgpu::Extent3d {
-                width: size.0,
-                height: size.1,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: Raymarcher::SDF_FORMAT,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
-            view_formats: &[],
-        })
-    }
+    const SHADER: ShaderFile = shader_file!("src/gi/raymarcher/shader.wgsl");
 
     pub fn new(
         device: &wgpu::Device,
-        window_size: (u32, u32),
+        texture_size: (u32, u32),
+        out_texture_format: wgpu::TextureFormat,
+        label: String,
+    ) -> Self {
+        Raymarcher::with_config(
+            device,
+            texture_size,
+            out_texture_format,
+            label,
+            RaymarcherConfig::default(),
+        )
+    }
+
+    pub fn with_config(
+        device: &wgpu::Device,
+        texture_size: (u32, u32),
         out_texture_format: wgpu::TextureFormat,
         label: String,
+        config: RaymarcherConfig,
     ) -> Self {
         let nearest_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("raymarcher nearest sampler"),
@@ -78,11 +84,6 @@ impl Raymarcher {
             mapped_at_creation: false,
         });
 
-        let sdf_texture = Raymarcher::create_sdf_texture(device, window_size);
-        let sdf_view = sdf_texture.create_view(&wgpu::TextureViewDescriptor::default());
-
-        let jfa = JFA::new(device, window_size);
-
         let uniform_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("raymarcher uniform bind group layout"),
             entries: &[
@@ -166,35 +167,29 @@ impl Raymarcher {
             ],
         });
 
-        let shader_module = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
-
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("texture renderer pipeline layout"),
             bind_group_layouts: &[&uniform_bgl, &textures_bgl],
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("raymarcher pipeline"),
-            layout: Some(&pipeline_layout),
-            module: &shader_module,
-            entry_point: "main",
-            compilation_options: Default::default(),
-        });
+        let pipeline = ComputePipeline::with_prefix(
+            device,
+            "raymarcher pipeline",
+            Raymarcher::SHADER,
+            SRGB_WGSL,
+            pipeline_layout,
+        );
 
         Raymarcher {
             label,
 
-            config: RaymarcherConfig { ray_count: 64 },
-            window_size,
+            config,
+            texture_size,
 
             uniform_buffer,
-            sdf_texture,
-            sdf_view,
             uniform_bind_group,
 
-            jfa,
-
             textures_bgl,
 
             pipeline,
@@ -204,6 +199,7 @@ impl Raymarcher {
     fn create_texture_bind_group(
         &self,
         device: &wgpu::Device,
+        sdf_view: &wgpu::TextureView,
         in_texture_view: &wgpu::TextureView,
         out_texture_view: &wgpu::TextureView,
     ) -> wgpu::BindGroup {
@@ -213,7 +209,7 @@ impl Raymarcher {
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&self.sdf_view),
+                    resource: wgpu::BindingResource::TextureView(sdf_view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
@@ -234,7 +230,9 @@ impl GIRenderer for Raymarcher {
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         in_texture: &wgpu::Texture,
+        sdf: &DistanceField,
         out_texture: &wgpu::Texture,
+        profiler: &GpuProfiler,
     ) {
         let uniform_data = RawUniformData {
             ray_count: self.config.ray_count,
@@ -244,27 +242,22 @@ impl GIRenderer for Raymarcher {
         let in_view = in_texture.create_view(&wgpu::TextureViewDescriptor::default());
         let out_view = out_texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        self.jfa.render(
-            device,
-            queue,
-            &in_view,
-            &self.sdf_view,
-            (in_texture.size().width, in_texture.size().height),
-        );
-
-        let textures_bind_group = self.create_texture_bind_group(device, &in_view, &out_view);
+        let textures_bind_group =
+            self.create_texture_bind_group(device, sdf.view(), &in_view, &out_view);
 
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
 
         {
-            let mut compute_pass =
-                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("raymarcher compute pass"),
+                timestamp_writes: profiler.compute_pass_timestamps("raymarcher"),
+            });
             compute_pass.set_pipeline(&self.pipeline);
             compute_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
             compute_pass.set_bind_group(1, &textures_bind_group, &[]);
             compute_pass.dispatch_workgroups(
-                u32::div_ceil(self.window_size.0, 16),
-                u32::div_ceil(self.window_size.1, 16),
+                u32::div_ceil(self.texture_size.0, 16),
+                u32::div_ceil(self.texture_size.1, 16),
                 1,
             );
         }
@@ -272,23 +265,31 @@ impl GIRenderer for Raymarcher {
         queue.submit(Some(encoder.finish()));
     }
 
-    fn render_egui(&mut self, ctx: &egui::Context, _: &wgpu::Device, _: &wgpu::Queue) {
+    fn render_egui(&mut self, ctx: &egui::Context, _: &wgpu::Device, _: &wgpu::Queue) -> bool {
+        let old_ray_count = self.config.ray_count;
         egui::Window::new(&self.label).show(ctx, |ui| {
             ui.heading("Ray count");
             ui.add(egui::Slider::new(&mut self.config.ray_count, 4..=8196).logarithmic(true));
         });
+        self.config.ray_count != old_ray_count
+    }
+
+    fn reload_shaders(&mut self, device: &wgpu::Device) -> Result<(), String> {
+        self.pipeline.reload(device)
     }
 
-    fn resize(&mut self, device: &wgpu::Device, new_size: (u32, u32)) {
-        self.window_size = new_size;
-        self.sdf_texture = Raymarcher::create_sdf_texture(device, new_size);
-        self.sdf_view = self
-            .sdf_texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-        self.jfa = JFA::new(device, new_size);
+    fn resize(&mut self, _: &wgpu::Device, new_size: (u32, u32)) {
+        self.texture_size = new_size;
     }
 
     fn label(&self) -> String {
         self.label.clone()
     }
+
+    fn session(&self) -> RendererSession {
+        RendererSession::Raymarcher {
+            label: self.label.clone(),
+            config: self.config,
+        }
+    }
 }
@@ -37,7 +37,7 @@ fn march_ray(start_pos: vec2f, dir: vec2f) -> vec4f {
         if dist < 1 {
             let color = textureSampleLevel(in_texture, nearest_sampler, to_tex(pos, texel), 0.);
             if color.a > 0.99 {
-                return color;
+                return vec4f(srgb_to_linear(color.rgb), color.a);
             }
         }
 
@@ -0,0 +1,5 @@
+// 8 bit images like the scene are stored srgb encoded, the same way as the pngs they're loaded from
+fn srgb_to_linear(color: vec3f) -> vec3f {
+    return select(pow((color + 0.055) / 1.055, vec3f(2.4)), color / 12.92, color <= vec3f(0.04045));
+}
+
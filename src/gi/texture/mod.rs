@@ -1,13 +1,23 @@
+use super::distance_field::DistanceField;
 use super::GIRenderer;
+use super::RendererSession;
+use super::SRGB_WGSL;
+use crate::profiler::GpuProfiler;
 use crate::screenpass::{self, ScreenPass};
+use crate::textureio;
+#[cfg(target_arch = "wasm32")]
+use crate::web;
 use egui_wgpu::wgpu;
 
 pub struct TextureRenderer {
     pub label: String,
 
     texture: wgpu::Texture,
+    image_path: Option<String>,
     sampler: wgpu::Sampler,
     screenpass: ScreenPass,
+    #[cfg(target_arch = "wasm32")]
+    picker: web::FilePicker,
 }
 
 impl TextureRenderer {
@@ -27,7 +37,12 @@ impl TextureRenderer {
             },
             wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
         ];
-        let shader_module = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("texture renderer shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                [SRGB_WGSL, include_str!("shader.wgsl")].concat().into(),
+            ),
+        });
 
         let screenpass = ScreenPass::new(
             device,
@@ -61,74 +76,25 @@ impl TextureRenderer {
             sampler,
             screenpass,
             texture,
+            image_path: None,
+            #[cfg(target_arch = "wasm32")]
+            picker: web::FilePicker::default(),
         }
     }
 
-    fn load_texture_from_file(
+    pub fn load_texture_from_file(
         &mut self,
         filename: String,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
     ) {
-        let texture_image = match image::load(
-            std::io::BufReader::new(match std::fs::File::open(filename) {
-                Ok(f) => f,
-                Err(e) => {
-                    println!("Error opening file: {}", e);
-                    return;
-                }
-            }),
-            image::ImageFormat::Png,
-        ) {
-            Ok(img) => img,
-            Err(e) => {
-                println!("Error loading image: {}", e);
-                return;
+        match textureio::load_texture(device, queue, &filename, "texture renderer texture") {
+            Ok(texture) => {
+                self.texture = texture;
+                self.image_path = Some(filename);
             }
-        };
-
-        let texture_rgba = texture_image.to_rgba8();
-        let dimensions = texture_rgba.dimensions();
-
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("scene texture"),
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            mip_level_count: 1,
-            sample_count: 1,
-            size: wgpu::Extent3d {
-                width: dimensions.0,
-                height: dimensions.1,
-                depth_or_array_layers: 1,
-            },
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
-                | wgpu::TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::COPY_DST
-                | wgpu::TextureUsages::STORAGE_BINDING,
-            view_formats: &[],
-        });
-
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &texture_rgba,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
-            },
-            wgpu::Extent3d {
-                width: dimensions.0,
-                height: dimensions.1,
-                depth_or_array_layers: 1,
-            },
-        );
-
-        self.texture = texture;
+            Err(e) => println!("{}", e),
+        }
     }
 }
 
@@ -138,7 +104,9 @@ impl GIRenderer for TextureRenderer {
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         _: &wgpu::Texture,
+        _: &DistanceField,
         out_texture: &wgpu::Texture,
+        profiler: &GpuProfiler,
     ) {
         let in_texture = &self.texture;
         self.screenpass
@@ -156,14 +124,44 @@ impl GIRenderer for TextureRenderer {
                     ops: wgpu::Operations::default(),
                     resolve_target: None,
                 })],
+                viewport: None,
+                timestamp_writes: profiler.render_pass_timestamps("texture"),
             });
     }
 
-    fn render_egui(&mut self, ctx: &egui::Context, device: &wgpu::Device, queue: &wgpu::Queue) {
+    fn render_egui(
+        &mut self,
+        ctx: &egui::Context,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> bool {
+        let mut changed = false;
+
+        // picked files have no path in the browser, so they aren't saved in sessions
+        #[cfg(target_arch = "wasm32")]
+        if let Some(bytes) = self.picker.take() {
+            match textureio::load_texture_from_memory(
+                device,
+                queue,
+                &bytes,
+                "texture renderer texture",
+            ) {
+                Ok(texture) => {
+                    self.texture = texture;
+                    self.image_path = None;
+                    changed = true;
+                }
+                Err(e) => log::error!("{}", e),
+            }
+        }
+
         egui::Window::new(&self.label)
             .default_size(egui::Vec2::new(1., 1.))
             .show(ctx, |ui| {
                 if ui.button("Load Image").clicked() {
+                    #[cfg(target_arch = "wasm32")]
+                    self.picker.open("image/*");
+                    #[cfg(not(target_arch = "wasm32"))]
                     if let Some(filename) = native_dialog::FileDialog::new()
                         .show_open_single_file()
                         .unwrap()
@@ -173,12 +171,22 @@ impl GIRenderer for TextureRenderer {
                             device,
                             queue,
                         );
+                        changed = true;
                     }
                 }
             });
+
+        changed
     }
 
     fn label(&self) -> String {
         self.label.clone()
     }
+
+    fn session(&self) -> RendererSession {
+        RendererSession::Texture {
+            label: self.label.clone(),
+            image_path: self.image_path.clone(),
+        }
+    }
 }
@@ -23,5 +23,6 @@ var texture_sampler: sampler;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4f {
-    return textureSample(in_texture, texture_sampler, in.tex_coord);
+    let color = textureSample(in_texture, texture_sampler, in.tex_coord);
+    return vec4f(srgb_to_linear(color.rgb), color.a);
 }
@@ -0,0 +1,239 @@
+use egui_wgpu::wgpu;
+
+/// features every part of the app relies on
+pub const REQUIRED_FEATURES: wgpu::Features = wgpu::Features::FLOAT32_FILTERABLE;
+
+/// features that are used when the adapter has them, but aren't needed
+pub const OPTIONAL_FEATURES: wgpu::Features =
+    wgpu::Features::TIMESTAMP_QUERY.union(wgpu::Features::PUSH_CONSTANTS);
+
+pub const ADAPTER_USAGE: &str = "\
+adapter options:
+    --backend <name>                vulkan, metal, dx12, gl or all (default: all)
+    --adapter <index|name>          adapter to use, see --list-adapters
+    --software                      use a software (fallback) adapter
+    --list-adapters                 print the available adapters and exit";
+
+/// which adapter to run on, parsed from the command line
+pub struct AdapterOptions {
+    pub backends: wgpu::Backends,
+    /// index into the adapter list, or a part of the adapter's name
+    pub adapter: Option<String>,
+    pub software: bool,
+    pub list_adapters: bool,
+}
+
+impl Default for AdapterOptions {
+    fn default() -> Self {
+        AdapterOptions {
+            backends: wgpu::Backends::all(),
+            adapter: None,
+            software: false,
+            list_adapters: false,
+        }
+    }
+}
+
+impl AdapterOptions {
+    /// handles `flag` if it's one of the adapter options, returns whether it was
+    pub fn parse_flag<'a>(
+        &mut self,
+        flag: &str,
+        args: &mut impl Iterator<Item = &'a String>,
+    ) -> Result<bool, String> {
+        match flag {
+            "--backend" => {
+                self.backends = match args.next().map(String::as_str) {
+                    Some("vulkan") => wgpu::Backends::VULKAN,
+                    Some("metal") => wgpu::Backends::METAL,
+                    Some("dx12") => wgpu::Backends::DX12,
+                    Some("gl") => wgpu::Backends::GL,
+                    Some("all") => wgpu::Backends::all(),
+                    other => return Err(format!("Unknown backend: {:?}", other)),
+                }
+            }
+            "--adapter" => {
+                self.adapter = Some(args.next().ok_or("Missing value for --adapter")?.clone())
+            }
+            "--software" => self.software = true,
+            "--list-adapters" => self.list_adapters = true,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn create_instance(&self) -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: self.backends,
+            ..Default::default()
+        })
+    }
+}
+
+fn describe_adapter(adapter: &wgpu::Adapter) -> String {
+    let info = adapter.get_info();
+    let missing = REQUIRED_FEATURES.difference(adapter.features());
+    format!(
+        "{} ({:?}, {:?}){}",
+        info.name,
+        info.backend,
+        info.device_type,
+        match missing.is_empty() {
+            true => String::new(),
+            false => format!(", missing {:?}", missing),
+        }
+    )
+}
+
+// the browser only hands out adapters through request_adapter
+fn enumerate_adapters(instance: &wgpu::Instance, backends: wgpu::Backends) -> Vec<wgpu::Adapter> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return instance.enumerate_adapters(backends);
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = (instance, backends);
+        vec![]
+    }
+}
+
+/// one line per adapter, numbered the way `--adapter` expects
+pub fn adapter_list(instance: &wgpu::Instance, backends: wgpu::Backends) -> String {
+    let adapters = enumerate_adapters(instance, backends);
+    if adapters.is_empty() {
+        return "    (none)".to_owned();
+    }
+    adapters
+        .iter()
+        .enumerate()
+        .map(|(i, adapter)| format!("    {}: {}", i, describe_adapter(adapter)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// picks an adapter according to the options
+///
+/// without an explicit adapter a hardware one with the required features is preferred,
+/// falling back to a software one if there's none
+pub async fn request_adapter(
+    instance: &wgpu::Instance,
+    options: &AdapterOptions,
+    compatible_surface: Option<&wgpu::Surface<'_>>,
+) -> Result<wgpu::Adapter, String> {
+    let not_found = |what: String| {
+        format!(
+            "{}, available adapters:\n{}",
+            what,
+            adapter_list(instance, options.backends)
+        )
+    };
+
+    if let Some(wanted) = &options.adapter {
+        let adapters = enumerate_adapters(instance, options.backends);
+        let adapter = match wanted.parse::<usize>() {
+            Ok(index) => adapters.into_iter().nth(index),
+            Err(_) => adapters.into_iter().find(|a| {
+                a.get_info()
+                    .name
+                    .to_lowercase()
+                    .contains(&wanted.to_lowercase())
+            }),
+        }
+        .ok_or_else(|| not_found(format!("No adapter matches {:?}", wanted)))?;
+
+        if compatible_surface.is_some_and(|surface| !adapter.is_surface_supported(surface)) {
+            return Err(format!(
+                "Adapter {} can't present to the window",
+                adapter.get_info().name
+            ));
+        }
+        return Ok(adapter);
+    }
+
+    let suitable = |adapter: &wgpu::Adapter| {
+        adapter.features().contains(REQUIRED_FEATURES)
+            && compatible_surface.is_none_or(|surface| adapter.is_surface_supported(surface))
+    };
+
+    let request = |force_fallback_adapter| {
+        instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface,
+            force_fallback_adapter,
+        })
+    };
+
+    if !options.software {
+        // request_adapter doesn't know about features, so it could pick
+        // a gpu that can't run the app over one that can
+        let hardware = enumerate_adapters(instance, options.backends)
+            .into_iter()
+            .filter(|a| a.get_info().device_type != wgpu::DeviceType::Cpu && suitable(a))
+            .min_by_key(|a| match a.get_info().device_type {
+                wgpu::DeviceType::DiscreteGpu => 0,
+                wgpu::DeviceType::IntegratedGpu => 1,
+                _ => 2,
+            });
+        if let Some(adapter) = hardware {
+            return Ok(adapter);
+        }
+
+        match request(false).await {
+            Some(adapter) if suitable(&adapter) => return Ok(adapter),
+            Some(adapter) => log::warn!(
+                "Adapter {} can't be used, trying a software adapter",
+                describe_adapter(&adapter)
+            ),
+            None => log::warn!("No hardware adapter found, trying a software adapter"),
+        }
+    }
+    request(true)
+        .await
+        .ok_or_else(|| not_found("No suitable adapter found".to_owned()))
+}
+
+pub async fn request_device(
+    adapter: &wgpu::Adapter,
+) -> Result<(wgpu::Device, wgpu::Queue), String> {
+    let missing = REQUIRED_FEATURES.difference(adapter.features());
+    if !missing.is_empty() {
+        return Err(format!(
+            "Adapter {} doesn't support the required features {:?}, \
+            try another one with --adapter or --software",
+            adapter.get_info().name,
+            missing
+        ));
+    }
+
+    let features = REQUIRED_FEATURES | (adapter.features() & OPTIONAL_FEATURES);
+    let adapter_limits = adapter.limits();
+    adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                required_features: features,
+                required_limits: wgpu::Limits {
+                    // the jfa step size, passed through a uniform buffer otherwise
+                    max_push_constant_size: match features.contains(wgpu::Features::PUSH_CONSTANTS)
+                    {
+                        true => 4,
+                        false => 0,
+                    },
+                    // the cascades are as big as the adapter allows
+                    max_buffer_size: adapter_limits.max_buffer_size,
+                    max_storage_buffer_binding_size: adapter_limits.max_storage_buffer_binding_size,
+                    max_texture_dimension_2d: adapter_limits.max_texture_dimension_2d,
+                    max_texture_array_layers: adapter_limits.max_texture_array_layers,
+                    ..Default::default()
+                },
+            },
+            None,
+        )
+        .await
+        .map_err(|e| {
+            format!(
+                "Couldn't create a device on {}: {}",
+                adapter.get_info().name,
+                e
+            )
+        })
+}
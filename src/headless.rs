@@ -0,0 +1,254 @@
+use crate::display::Display;
+use crate::gi::distance_field::DistanceField;
+use crate::gi::path_tracer::{PathTracer, PathTracerConfig};
+use crate::gi::radiance_cascades::config::{
+    CascadePrecision, CascadeStorage, ProbeLayout, RingingFix,
+};
+use crate::gi::radiance_cascades::{RCConfig, RadianceCascades};
+use crate::gi::raymarcher::{Raymarcher, RaymarcherConfig};
+use crate::gi::GIRenderer;
+use crate::gpu;
+use crate::profiler::GpuProfiler;
+use crate::textureio;
+use crate::State;
+use egui_wgpu::wgpu;
+
+const USAGE: &str = "\
+usage: RCR render <scene image> <output image> [options]
+
+renders a scene without opening a window and writes the result to disk,
+an .exr output keeps the raw radiance, other formats are clamped to 8 bits
+
+options:
+    --renderer <rc|raymarcher|pathtracer>
+                                    renderer to use (default: rc)
+
+radiance cascades options:
+    --c0-rays <n>
+    --c0-spacing <px>
+    --c0-raylength <px>
+    --angular-scaling <n>
+    --spatial-scaling <f>
+    --cascades <n>
+    --probe-layout <offset|stacked>
+    --ringing-fix <vanilla|bilinear>
+    --cascade-storage <buffer|texture>
+                                    texture fits bigger cascades (default: buffer)
+    --cascade-precision <f16|f32>   (default: f16)
+
+raymarcher options:
+    --rays <n>
+
+path tracer options, it renders until the samples are reached:
+    --samples <n>                   rays per pixel in total
+    --rays-per-frame <n>";
+
+enum RendererChoice {
+    RadianceCascades,
+    Raymarcher,
+    PathTracer,
+}
+
+struct HeadlessOptions {
+    scene_path: String,
+    out_path: String,
+    adapter: gpu::AdapterOptions,
+
+    renderer: RendererChoice,
+    rc_config: RCConfig,
+    raymarcher_config: RaymarcherConfig,
+    path_tracer_config: PathTracerConfig,
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<&String>) -> Result<T, String> {
+    let value = value.ok_or(format!("Missing value for {}", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {}: {}", flag, value))
+}
+
+impl HeadlessOptions {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut positional = vec![];
+        let mut options = HeadlessOptions {
+            scene_path: String::new(),
+            out_path: String::new(),
+            adapter: gpu::AdapterOptions::default(),
+
+            renderer: RendererChoice::RadianceCascades,
+            rc_config: RCConfig::default(),
+            raymarcher_config: RaymarcherConfig::default(),
+            path_tracer_config: PathTracerConfig::default(),
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if options.adapter.parse_flag(arg, &mut args)? {
+                continue;
+            }
+
+            let rc = &mut options.rc_config;
+            match arg.as_str() {
+                "--renderer" => {
+                    options.renderer = match args.next().map(String::as_str) {
+                        Some("rc") => RendererChoice::RadianceCascades,
+                        Some("raymarcher") => RendererChoice::Raymarcher,
+                        Some("pathtracer") => RendererChoice::PathTracer,
+                        other => return Err(format!("Unknown renderer: {:?}", other)),
+                    }
+                }
+                "--c0-rays" => rc.c0_rays = parse_value(arg, args.next())?,
+                "--c0-spacing" => rc.c0_spacing = parse_value(arg, args.next())?,
+                "--c0-raylength" => rc.c0_raylength = parse_value(arg, args.next())?,
+                "--angular-scaling" => rc.angular_scaling = parse_value(arg, args.next())?,
+                "--spatial-scaling" => rc.spatial_scaling = parse_value(arg, args.next())?,
+                "--cascades" => rc.num_cascades = parse_value(arg, args.next())?,
+                "--probe-layout" => {
+                    rc.probe_layout = match args.next().map(String::as_str) {
+                        Some("offset") => ProbeLayout::Offset,
+                        Some("stacked") => ProbeLayout::Stacked,
+                        other => return Err(format!("Unknown probe layout: {:?}", other)),
+                    }
+                }
+                "--ringing-fix" => {
+                    rc.ringing_fix = match args.next().map(String::as_str) {
+                        Some("vanilla") => RingingFix::Vanilla,
+                        Some("bilinear") => RingingFix::Bilinear,
+                        other => return Err(format!("Unknown ringing fix: {:?}", other)),
+                    }
+                }
+                "--cascade-storage" => {
+                    rc.storage = match args.next().map(String::as_str) {
+                        Some("buffer") => CascadeStorage::Buffer,
+                        Some("texture") => CascadeStorage::Texture,
+                        other => return Err(format!("Unknown cascade storage: {:?}", other)),
+                    }
+                }
+                "--cascade-precision" => {
+                    rc.precision = match args.next().map(String::as_str) {
+                        Some("f16") => CascadePrecision::Half,
+                        Some("f32") => CascadePrecision::Full,
+                        other => return Err(format!("Unknown cascade precision: {:?}", other)),
+                    }
+                }
+                "--rays" => options.raymarcher_config.ray_count = parse_value(arg, args.next())?,
+                "--samples" => {
+                    options.path_tracer_config.max_samples = parse_value(arg, args.next())?
+                }
+                "--rays-per-frame" => {
+                    options.path_tracer_config.rays_per_frame = parse_value(arg, args.next())?;
+                    if options.path_tracer_config.rays_per_frame == 0 {
+                        return Err("--rays-per-frame has to be at least 1".to_owned());
+                    }
+                }
+                flag if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+                _ => positional.push(arg.clone()),
+            }
+        }
+
+        if options.adapter.list_adapters {
+            return Ok(options);
+        }
+        options.rc_config.validate()?;
+
+        match positional.as_slice() {
+            [scene, out] => {
+                options.scene_path = scene.clone();
+                options.out_path = out.clone();
+                Ok(options)
+            }
+            _ => Err("Expected a scene image and an output image".to_owned()),
+        }
+    }
+}
+
+/// entry point of the `render` subcommand
+pub async fn render(args: &[String]) -> Result<(), String> {
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}\n\n{}", USAGE, gpu::ADAPTER_USAGE);
+        return Ok(());
+    }
+    let options = HeadlessOptions::parse(args)
+        .map_err(|e| format!("{}\n\n{}\n\n{}", e, USAGE, gpu::ADAPTER_USAGE))?;
+
+    let instance = options.adapter.create_instance();
+    if options.adapter.list_adapters {
+        println!("{}", gpu::adapter_list(&instance, options.adapter.backends));
+        return Ok(());
+    }
+
+    let adapter = gpu::request_adapter(&instance, &options.adapter, None).await?;
+    log::info!("Rendering on {:?}", adapter.get_info());
+
+    let (device, queue) = gpu::request_device(&adapter).await?;
+
+    let scene_texture =
+        textureio::load_texture(&device, &queue, &options.scene_path, "scene texture")?;
+    let size = (scene_texture.width(), scene_texture.height());
+
+    if matches!(options.renderer, RendererChoice::RadianceCascades)
+        && !RadianceCascades::fits(&device, size, &options.rc_config)
+    {
+        return Err(format!(
+            "The cascades are too big for this device, {} elements ({:.1} MiB){}",
+            options.rc_config.get_max_cascade_size(size),
+            options.rc_config.get_cascade_memory(size) as f64 / (1024. * 1024.),
+            match options.rc_config.storage {
+                CascadeStorage::Buffer => ", try --cascade-storage texture",
+                CascadeStorage::Texture => "",
+            }
+        ));
+    }
+
+    let renderer: Box<dyn GIRenderer> = match options.renderer {
+        RendererChoice::RadianceCascades => Box::new(RadianceCascades::with_config(
+            &device,
+            size,
+            "RC".to_owned(),
+            options.rc_config,
+        )),
+        RendererChoice::Raymarcher => Box::new(Raymarcher::with_config(
+            &device,
+            size,
+            wgpu::TextureFormat::Rgba16Float,
+            "Raymarcher".to_owned(),
+            options.raymarcher_config,
+        )),
+        RendererChoice::PathTracer => Box::new(PathTracer::with_config(
+            &device,
+            size,
+            "Path Tracer".to_owned(),
+            options.path_tracer_config,
+        )),
+    };
+
+    let out_texture = State::create_out_texture(&device, size);
+    // never starts a frame, so no queries are recorded
+    let profiler = GpuProfiler::new(&device, &queue);
+    let sdf = DistanceField::new(&device, size);
+    sdf.update(&device, &queue, &scene_texture, &profiler);
+    renderer.render(
+        &device,
+        &queue,
+        &scene_texture,
+        &sdf,
+        &out_texture,
+        &profiler,
+    );
+    while renderer.converging() {
+        renderer.render(
+            &device,
+            &queue,
+            &scene_texture,
+            &sdf,
+            &out_texture,
+            &profiler,
+        );
+        // keeps the queue short, a long one can trip the gpu watchdog
+        device.poll(wgpu::Maintain::Wait);
+    }
+
+    // the default display settings, clamped and srgb encoded
+    let display = Display::new(&device, wgpu::TextureFormat::Rgba8Unorm);
+    textureio::save_output(&device, &queue, &out_texture, &display, &options.out_path)
+}
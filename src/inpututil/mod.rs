@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use std::time::Instant;
+use web_time::Instant;
 use winit::event::MouseButton;
 use winit::event::WindowEvent;
 use winit::keyboard::PhysicalKey;
@@ -12,6 +12,8 @@ pub struct InputController {
 
     last_mouse_pos: (f32, f32),
     cur_mouse_pos: (f32, f32),
+    // wheel lines since the last frame, up is positive
+    scroll: f32,
 }
 
 impl Default for InputController {
@@ -25,6 +27,7 @@ impl Default for InputController {
 
             last_mouse_pos: (-1., -1.),
             cur_mouse_pos: (-1., -1.),
+            scroll: 0.,
         }
     }
 }
@@ -64,21 +67,35 @@ impl InputController {
                 if state.is_pressed() {
                     self.mouse_buttons_pressed.insert(*button, true);
                 } else {
-                    self.mouse_buttons_pressed.remove(&button);
+                    self.mouse_buttons_pressed.remove(button);
                 }
 
                 true
             }
+            WindowEvent::MouseWheel {
+                device_id: _,
+                delta,
+                phase: _,
+            } => {
+                self.scroll += match delta {
+                    winit::event::MouseScrollDelta::LineDelta(_, y) => *y,
+                    // roughly what a line is worth on most systems
+                    winit::event::MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 50.,
+                };
+                true
+            }
             _ => false,
         }
     }
 
-    // must be called at the start of every frame
+    // must be called at the end of every frame, so the next one sees
+    // the presses and movement that happen until then
     pub fn init_frame(&mut self) {
         self.last_frame = self.cur_frame;
         self.cur_frame = Instant::now();
 
         self.last_mouse_pos = self.cur_mouse_pos;
+        self.scroll = 0.;
 
         self.keys_pressed.iter_mut().for_each(|(_, v)| *v = false);
         self.mouse_buttons_pressed
@@ -105,6 +122,11 @@ impl InputController {
             .unwrap_or(&false)
     }
 
+    pub fn ctrl_pressed(&self) -> bool {
+        self.key_pressed(winit::keyboard::KeyCode::ControlLeft)
+            || self.key_pressed(winit::keyboard::KeyCode::ControlRight)
+    }
+
     pub fn get_deltatime(&self) -> std::time::Duration {
         self.cur_frame - self.last_frame
     }
@@ -119,4 +141,8 @@ impl InputController {
     pub fn get_mouse_pos(&self) -> (f32, f32) {
         self.cur_mouse_pos
     }
+
+    pub fn get_scroll(&self) -> f32 {
+        self.scroll
+    }
 }
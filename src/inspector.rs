@@ -0,0 +1,217 @@
+use crate::viewport::Viewport;
+use egui_wgpu::wgpu;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// everything shown about the hovered pixel
+#[derive(Clone)]
+struct PixelInfo {
+    pos: (u32, u32),
+    radiance: [f32; 4],
+    scene_color: [u8; 4],
+    /// distance to the nearest wall
+    sdf: Option<f32>,
+}
+
+/// a pixel that's being copied to the readback buffer
+struct InFlight {
+    pos: (u32, u32),
+    has_sdf: bool,
+    mapped: Arc<AtomicBool>,
+}
+
+/// reads back the pixel under the cursor, the values show up a frame or two later
+pub struct Inspector {
+    readback_buffer: wgpu::Buffer,
+    in_flight: Option<InFlight>,
+    hovered: Option<PixelInfo>,
+    // the textures changed since the last readback started
+    dirty: bool,
+}
+
+impl Inspector {
+    // every texture gets its own row of the readback buffer, copies need aligned offsets
+    const RADIANCE_OFFSET: u64 = 0;
+    const SCENE_OFFSET: u64 = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64;
+    const SDF_OFFSET: u64 = 2 * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64;
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        Inspector {
+            readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("inspector readback buffer"),
+                size: 3 * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            in_flight: None,
+            hovered: None,
+            dirty: true,
+        }
+    }
+
+    /// whether a readback is still on its way, and another frame is needed to show it
+    pub fn waiting(&self) -> bool {
+        self.in_flight.is_some()
+    }
+
+    fn finish_readback(&mut self, device: &wgpu::Device) {
+        device.poll(wgpu::Maintain::Poll);
+        let Some(in_flight) = self
+            .in_flight
+            .take_if(|in_flight| in_flight.mapped.load(Ordering::Acquire))
+        else {
+            return;
+        };
+
+        {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let f32_at = |offset: u64| {
+                let offset = offset as usize;
+                f32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+            };
+            let radiance = core::array::from_fn(|i| {
+                let offset = Inspector::RADIANCE_OFFSET as usize + i * 2;
+                half::f16::from_le_bytes([data[offset], data[offset + 1]]).to_f32()
+            });
+            let scene_offset = Inspector::SCENE_OFFSET as usize;
+
+            self.hovered = Some(PixelInfo {
+                pos: in_flight.pos,
+                radiance,
+                scene_color: data[scene_offset..scene_offset + 4].try_into().unwrap(),
+                sdf: in_flight.has_sdf.then(|| f32_at(Inspector::SDF_OFFSET)),
+            });
+        }
+        self.readback_buffer.unmap();
+    }
+
+    /// starts reading back the pixel at `pos`, in scene pixels, if it or the textures changed
+    ///
+    /// `out_texture` is Rgba16Float, `scene_texture` Rgba8Unorm and the sdf R32Float
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        pos: Option<(u32, u32)>,
+        textures_changed: bool,
+        out_texture: &wgpu::Texture,
+        scene_texture: &wgpu::Texture,
+        sdf_texture: &wgpu::Texture,
+    ) {
+        self.finish_readback(device);
+        self.dirty |= textures_changed;
+
+        let Some(pos) = pos else {
+            self.hovered = None;
+            return;
+        };
+        let up_to_date = !self.dirty && self.hovered.as_ref().is_some_and(|p| p.pos == pos);
+        if self.in_flight.is_some() || up_to_date {
+            return;
+        }
+        self.dirty = false;
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("inspector encoder"),
+        });
+        let mut copy = |texture: &wgpu::Texture, offset: u64| {
+            encoder.copy_texture_to_buffer(
+                wgpu::ImageCopyTexture {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: pos.0,
+                        y: pos.1,
+                        z: 0,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::ImageCopyBuffer {
+                    buffer: &self.readback_buffer,
+                    layout: wgpu::ImageDataLayout {
+                        offset,
+                        bytes_per_row: None,
+                        rows_per_image: None,
+                    },
+                },
+                wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+            );
+        };
+
+        copy(out_texture, Inspector::RADIANCE_OFFSET);
+        copy(scene_texture, Inspector::SCENE_OFFSET);
+        // the sdf can lag behind the scene size for a frame after a resize
+        let has_sdf = pos.0 < sdf_texture.width() && pos.1 < sdf_texture.height();
+        if has_sdf {
+            copy(sdf_texture, Inspector::SDF_OFFSET);
+        }
+        queue.submit(Some(encoder.finish()));
+
+        let mapped = Arc::new(AtomicBool::new(false));
+        let mapped_clone = mapped.clone();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if result.is_ok() {
+                    mapped_clone.store(true, Ordering::Release);
+                }
+            });
+        self.in_flight = Some(InFlight {
+            pos,
+            has_sdf,
+            mapped,
+        });
+    }
+
+    pub fn render_egui(&mut self, ctx: &egui::Context, viewport: &mut Viewport) {
+        egui::Window::new("Inspector")
+            .default_size(egui::Vec2::new(1., 1.))
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Zoom: {:.2}x, {:.2} window px per scene px",
+                    viewport.zoom,
+                    viewport.scale()
+                ));
+                ui.label("scroll to zoom, drag with the middle mouse button to pan");
+                if ui.button("Reset view").clicked() {
+                    viewport.reset_view();
+                }
+                ui.separator();
+
+                let Some(pixel) = &self.hovered else {
+                    ui.label("Hover over the scene to inspect a pixel");
+                    return;
+                };
+
+                egui::Grid::new("inspector grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Pixel");
+                        ui.label(format!("{}, {}", pixel.pos.0, pixel.pos.1));
+                        ui.end_row();
+
+                        let [r, g, b, _] = pixel.radiance;
+                        ui.label("Radiance");
+                        ui.monospace(format!("{:.5} {:.5} {:.5}", r, g, b));
+                        ui.end_row();
+
+                        let [r, g, b, a] = pixel.scene_color;
+                        ui.label("Scene color");
+                        ui.monospace(format!("{} {} {}, alpha {}", r, g, b, a));
+                        ui.end_row();
+
+                        ui.label("SDF distance");
+                        match &pixel.sdf {
+                            Some(distance) => ui.monospace(format!("{:.2} px", distance)),
+                            None => ui.label("not computed yet"),
+                        };
+                        ui.end_row();
+                    });
+            });
+    }
+}
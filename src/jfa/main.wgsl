@@ -1,11 +1,9 @@
-const nonexistent_coord: f32 = -2e9;
-
-var<push_constant> stepsize: u32;
+const nonexistent_coord: f32 = -65504.;
 
 @group(0) @binding(0)
 var in_texture: texture_2d<f32>;
 @group(0) @binding(1)
-var out_texture: texture_storage_2d<rg16float, write>;
+var out_texture: texture_storage_2d<rgba16float, write>;
 @group(0) @binding(2)
 var tampler: sampler;
 
@@ -16,6 +14,13 @@ fn main(@builtin(global_invocation_id) id: vec3u) {
     let texel = 1. / vec2f(in_dims);
     let pixel_pos = id.xy;
 
+    let own = textureLoad(in_texture, pixel_pos, 0);
+    // left alone by a partial update
+    if own.z == 0. {
+        textureStore(out_texture, pixel_pos, own);
+        return;
+    }
+
     var best_dist: f32 = 2e9;
     var best_pos: vec2f = vec2f(nonexistent_coord);
 
@@ -33,5 +38,5 @@ fn main(@builtin(global_invocation_id) id: vec3u) {
         }
     }
 
-    textureStore(out_texture, pixel_pos, vec4f(best_pos, 0., 0.));
+    textureStore(out_texture, pixel_pos, vec4f(best_pos, 1., 0.));
 }
@@ -1,32 +1,73 @@
+use crate::profiler::GpuProfiler;
+use crate::scene::DirtyRect;
+use crate::shaders::{self, shader_file, ComputePipeline, ShaderFile};
 use egui_wgpu::wgpu;
 
+/// how the main pass gets its step size
+enum StepSize {
+    PushConstant,
+    /// one u32 per main pass, each at an aligned offset that's picked with a dynamic offset
+    Uniform {
+        buffer: wgpu::Buffer,
+        bind_group: wgpu::BindGroup,
+        stride: u32,
+    },
+}
+
+/// the rect passed to the prepare pass
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct RawDirtyRect {
+    min: [f32; 2],
+    max: [f32; 2],
+}
+
+#[allow(clippy::upper_case_acronyms)]
 pub struct JFA {
+    sampler: wgpu::Sampler,
+
+    dirty_buffer: wgpu::Buffer,
     in_texture_bgl: wgpu::BindGroupLayout,
-    prepare_pipeline: wgpu::ComputePipeline,
+    prepare_pipeline: ComputePipeline,
 
+    main_bgl: wgpu::BindGroupLayout,
+    temp_textures: [wgpu::Texture; 2],
     main_bind_groups: [wgpu::BindGroup; 2],
-    main_pipeline: wgpu::ComputePipeline,
+    main_pipeline: ComputePipeline,
+    stepsize: StepSize,
 
     out_texture_bgl: wgpu::BindGroupLayout,
-    final_pipeline: wgpu::ComputePipeline,
+    final_pipeline: ComputePipeline,
 }
 
 impl JFA {
-    const TEMP_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+    const TEMP_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    // enough for a 8192x8192 texture with room to spare
+    const MAX_STEPS: u32 = 32;
 
-    fn create_temp_textures(device: &wgpu::Device, window_size: (u32, u32)) -> [wgpu::Texture; 2] {
+    const STEPSIZE_PUSH_CONSTANT: &'static str = "var<push_constant> stepsize: u32;\n";
+    const STEPSIZE_UNIFORM: &'static str = "@group(1) @binding(0)\nvar<uniform> stepsize: u32;\n";
+
+    const PREPARE_SHADER: ShaderFile = shader_file!("src/jfa/prepare.wgsl");
+    const MAIN_SHADER: ShaderFile = shader_file!("src/jfa/main.wgsl");
+    const FINAL_SHADER: ShaderFile = shader_file!("src/jfa/final.wgsl");
+
+    fn create_temp_textures(device: &wgpu::Device, texture_size: (u32, u32)) -> [wgpu::Texture; 2] {
         core::array::from_fn(|_| {
             device.create_texture(&wgpu::TextureDescriptor {
                 label: Some("a jfa temp texture"),
                 size: wgpu::Extent3d {
-                    width: window_size.0,
-                    height: window_size.1,
+                    width: texture_size.0,
+                    height: texture_size.1,
                     depth_or_array_layers: 1,
                 },
                 format: JFA::TEMP_TEXTURE_FORMAT,
+                // partial updates copy the last result between them
                 usage: wgpu::TextureUsages::STORAGE_BINDING
                     | wgpu::TextureUsages::TEXTURE_BINDING
-                    | wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    | wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::COPY_SRC
+                    | wgpu::TextureUsages::COPY_DST,
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
@@ -35,7 +76,40 @@ impl JFA {
         })
     }
 
-    pub fn new(device: &wgpu::Device, window_size: (u32, u32)) -> Self {
+    fn create_main_bind_groups(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        temp_textures: &[wgpu::Texture; 2],
+    ) -> [wgpu::BindGroup; 2] {
+        let temp_texture_views = temp_textures
+            .iter()
+            .map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()))
+            .collect::<Vec<wgpu::TextureView>>();
+
+        core::array::from_fn(|i| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("a jfa main bind group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&temp_texture_views[1 - i]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&temp_texture_views[i]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                ],
+            })
+        })
+    }
+
+    pub fn new(device: &wgpu::Device, texture_size: (u32, u32)) -> Self {
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("jfa smapler"),
             address_mode_u: wgpu::AddressMode::ClampToEdge,
@@ -43,29 +117,39 @@ impl JFA {
             ..Default::default()
         });
 
-        let temp_textures = JFA::create_temp_textures(device, window_size);
-        let temp_texture_views = temp_textures
-            .iter()
-            .map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()))
-            .collect::<Vec<wgpu::TextureView>>();
+        let use_push_constants = device.features().contains(wgpu::Features::PUSH_CONSTANTS);
 
-        let prepare_shader_module =
-            device.create_shader_module(wgpu::include_wgsl!("prepare.wgsl"));
-        let main_shader_module = device.create_shader_module(wgpu::include_wgsl!("main.wgsl"));
-        let final_shader_module = device.create_shader_module(wgpu::include_wgsl!("final.wgsl"));
+        let dirty_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("jfa dirty rect buffer"),
+            size: std::mem::size_of::<RawDirtyRect>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
 
         let in_texture_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("jfa in texture bgl"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::COMPUTE,
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    multisampled: false,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
         });
 
         let main_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -100,26 +184,9 @@ impl JFA {
             ],
         });
 
-        let main_bind_groups: [wgpu::BindGroup; 2] = core::array::from_fn(|i| {
-            device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("a jfa main bind group"),
-                layout: &main_bgl,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&temp_texture_views[1 - i]),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::TextureView(&temp_texture_views[i]),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: wgpu::BindingResource::Sampler(&sampler),
-                    },
-                ],
-            })
-        });
+        let temp_textures = JFA::create_temp_textures(device, texture_size);
+        let main_bind_groups =
+            JFA::create_main_bind_groups(device, &main_bgl, &sampler, &temp_textures);
 
         let out_texture_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("jfa out texture bgl"),
@@ -128,7 +195,7 @@ impl JFA {
                 visibility: wgpu::ShaderStages::COMPUTE,
                 ty: wgpu::BindingType::StorageTexture {
                     access: wgpu::StorageTextureAccess::WriteOnly,
-                    format: wgpu::TextureFormat::R16Float,
+                    format: wgpu::TextureFormat::R32Float,
                     view_dimension: wgpu::TextureViewDimension::D2,
                 },
                 count: None,
@@ -142,30 +209,83 @@ impl JFA {
                 push_constant_ranges: &[],
             });
 
-        let prepare_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("jfa prepare pipeline"),
-            layout: Some(&prepare_pipeline_layout),
-            module: &prepare_shader_module,
-            entry_point: "main",
-            compilation_options: Default::default(),
-        });
+        let prepare_pipeline = ComputePipeline::new(
+            device,
+            "jfa prepare pipeline",
+            JFA::PREPARE_SHADER,
+            prepare_pipeline_layout,
+        );
 
-        let main_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("jfa main pipeline layout"),
-            bind_group_layouts: &[&main_bgl],
-            push_constant_ranges: &[wgpu::PushConstantRange {
-                stages: wgpu::ShaderStages::COMPUTE,
-                range: 0..4,
+        let stepsize_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("jfa stepsize bgl"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(4),
+                },
+                count: None,
             }],
         });
 
-        let main_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("jfa main compute pipeline"),
-            layout: Some(&main_pipeline_layout),
-            module: &main_shader_module,
-            entry_point: "main",
-            compilation_options: Default::default(),
-        });
+        let main_pipeline_layout = match use_push_constants {
+            true => device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("jfa main pipeline layout"),
+                bind_group_layouts: &[&main_bgl],
+                push_constant_ranges: &[wgpu::PushConstantRange {
+                    stages: wgpu::ShaderStages::COMPUTE,
+                    range: 0..4,
+                }],
+            }),
+            false => device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("jfa main pipeline layout"),
+                bind_group_layouts: &[&main_bgl, &stepsize_bgl],
+                push_constant_ranges: &[],
+            }),
+        };
+
+        let stepsize = match use_push_constants {
+            true => StepSize::PushConstant,
+            false => {
+                let stride = device.limits().min_uniform_buffer_offset_alignment;
+                let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("jfa stepsize buffer"),
+                    size: (stride * JFA::MAX_STEPS) as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("jfa stepsize bind group"),
+                    layout: &stepsize_bgl,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: &buffer,
+                            offset: 0,
+                            size: wgpu::BufferSize::new(4),
+                        }),
+                    }],
+                });
+                StepSize::Uniform {
+                    buffer,
+                    bind_group,
+                    stride,
+                }
+            }
+        };
+
+        let main_pipeline = ComputePipeline::with_prefix(
+            device,
+            "jfa main compute pipeline",
+            JFA::MAIN_SHADER,
+            match use_push_constants {
+                true => JFA::STEPSIZE_PUSH_CONSTANT,
+                false => JFA::STEPSIZE_UNIFORM,
+            },
+            main_pipeline_layout,
+        );
 
         let final_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -174,26 +294,87 @@ impl JFA {
                 push_constant_ranges: &[],
             });
 
-        let final_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("jfa final pipeline"),
-            layout: Some(&final_pipeline_layout),
-            module: &final_shader_module,
-            entry_point: "main",
-            compilation_options: Default::default(),
-        });
+        let final_pipeline = ComputePipeline::new(
+            device,
+            "jfa final pipeline",
+            JFA::FINAL_SHADER,
+            final_pipeline_layout,
+        );
 
         JFA {
+            sampler,
+
+            dirty_buffer,
             in_texture_bgl,
             prepare_pipeline,
 
+            main_bgl,
+            temp_textures,
             main_bind_groups,
             main_pipeline,
+            stepsize,
 
             out_texture_bgl,
             final_pipeline,
         }
     }
 
+    pub fn resize(&mut self, device: &wgpu::Device, texture_size: (u32, u32)) {
+        self.temp_textures = JFA::create_temp_textures(device, texture_size);
+        self.main_bind_groups = JFA::create_main_bind_groups(
+            device,
+            &self.main_bgl,
+            &self.sampler,
+            &self.temp_textures,
+        );
+    }
+
+    /// closest seed position of every pixel after the last render, in pixels
+    ///
+    /// Rgba16Float, pixels with no seed at all hold -65504, the lowest f16
+    pub fn seed_texture(&self) -> &wgpu::Texture {
+        // the final pass reads from the first one
+        &self.temp_textures[0]
+    }
+
+    pub fn reload_shaders(&mut self, device: &wgpu::Device) -> Result<(), String> {
+        shaders::join_errors([
+            self.prepare_pipeline.reload(device),
+            self.main_pipeline.reload(device),
+            self.final_pipeline.reload(device),
+        ])
+    }
+
+    /// step sizes of the main passes, always an even number of them
+    fn step_sizes(texture_size: (u32, u32)) -> Vec<u32> {
+        let mut stepsize: u32 = {
+            let (w, h) = texture_size;
+            f32::sqrt((w * w + h * h) as f32) as u32
+        };
+
+        let mut steps = vec![];
+        for i in 1.. {
+            steps.push(stepsize);
+
+            stepsize /= 2;
+            // TODO: make a nonhacky way to end on temp_textures[0]
+            // or just dont do that at all i dont need it why am i doing this
+            if stepsize == 0 {
+                if i % 2 == 1 {
+                    stepsize = 1;
+                } else {
+                    break;
+                }
+            }
+        }
+        steps
+    }
+
+    /// writes the distance to the closest non transparent pixel of the in texture to the out texture
+    ///
+    /// with `dirty` only the pixels a change inside it can affect are updated, the temp textures
+    /// and the out texture have to hold the last render of a scene that only changed there
+    #[allow(clippy::too_many_arguments)]
     pub fn render(
         &self,
         device: &wgpu::Device,
@@ -202,19 +383,34 @@ impl JFA {
         out_texture_view: &wgpu::TextureView,
         // TODO: not need the copy
         texture_size: impl Into<(u32, u32)> + Copy,
+        dirty: Option<DirtyRect>,
+        profiler: &GpuProfiler,
     ) {
         let num_workgroups = {
             let (w, h) = texture_size.into();
             (u32::div_ceil(w, 16), u32::div_ceil(h, 16))
         };
 
+        let dirty_rect = dirty.unwrap_or(DirtyRect::ALL);
+        let raw_dirty_rect = RawDirtyRect {
+            min: [dirty_rect.min.0 as f32, dirty_rect.min.1 as f32],
+            max: [dirty_rect.max.0 as f32, dirty_rect.max.1 as f32],
+        };
+        queue.write_buffer(&self.dirty_buffer, 0, bytemuck::bytes_of(&raw_dirty_rect));
+
         let in_texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("jfa in texture bind group"),
             layout: &self.in_texture_bgl,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::TextureView(&in_texture_view),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(in_texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.dirty_buffer.as_entire_binding(),
+                },
+            ],
         });
 
         let out_texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -222,48 +418,67 @@ impl JFA {
             layout: &self.out_texture_bgl,
             entries: &[wgpu::BindGroupEntry {
                 binding: 0,
-                resource: wgpu::BindingResource::TextureView(&out_texture_view),
+                resource: wgpu::BindingResource::TextureView(out_texture_view),
             }],
         });
 
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
 
+        // the prepare pass reads the old seeds from the second texture, the last pass wrote the first
+        if dirty.is_some() {
+            encoder.copy_texture_to_texture(
+                self.temp_textures[0].as_image_copy(),
+                self.temp_textures[1].as_image_copy(),
+                self.temp_textures[0].size(),
+            );
+        }
+
         {
-            let mut prepare_pass =
-                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            let mut prepare_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("jfa prepare pass"),
+                timestamp_writes: profiler.compute_pass_timestamps("jfa prepare"),
+            });
             prepare_pass.set_pipeline(&self.prepare_pipeline);
             prepare_pass.set_bind_group(0, &in_texture_bind_group, &[]);
             prepare_pass.set_bind_group(1, &self.main_bind_groups[0], &[]);
             prepare_pass.dispatch_workgroups(num_workgroups.0, num_workgroups.1, 1);
         }
 
-        let mut stepsize: u32 = {
-            let (w, h) = texture_size.into();
-            f32::sqrt((w * w + h * h) as f32) as u32
-        };
+        let steps = JFA::step_sizes(texture_size.into());
+        assert!(steps.len() as u32 <= JFA::MAX_STEPS);
 
-        for i in 1.. {
-            let mut main_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
-            main_pass.set_pipeline(&self.main_pipeline);
-            main_pass.set_bind_group(0, &self.main_bind_groups[i % 2], &[]);
-            main_pass.set_push_constants(0, &stepsize.to_le_bytes());
-            main_pass.dispatch_workgroups(num_workgroups.0, num_workgroups.1, 1);
+        if let StepSize::Uniform { buffer, stride, .. } = &self.stepsize {
+            let mut data = vec![0u8; (stride * JFA::MAX_STEPS) as usize];
+            for (i, stepsize) in steps.iter().enumerate() {
+                let offset = i * *stride as usize;
+                data[offset..offset + 4].copy_from_slice(&stepsize.to_le_bytes());
+            }
+            queue.write_buffer(buffer, 0, &data);
+        }
 
-            stepsize /= 2;
-            // TODO: make a nonhacky way to end on temp_textures[0]
-            // or just dont do that at all i dont need it why am i doing this
-            if stepsize == 0 {
-                if i % 2 == 1 {
-                    stepsize = 1;
-                } else {
-                    break;
+        for (i, stepsize) in steps.iter().enumerate() {
+            let mut main_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("jfa main pass"),
+                timestamp_writes: profiler.compute_pass_timestamps("jfa main"),
+            });
+            main_pass.set_pipeline(&self.main_pipeline);
+            main_pass.set_bind_group(0, &self.main_bind_groups[(i + 1) % 2], &[]);
+            match &self.stepsize {
+                StepSize::PushConstant => {
+                    main_pass.set_push_constants(0, &stepsize.to_le_bytes());
                 }
+                StepSize::Uniform {
+                    bind_group, stride, ..
+                } => main_pass.set_bind_group(1, bind_group, &[i as u32 * stride]),
             }
+            main_pass.dispatch_workgroups(num_workgroups.0, num_workgroups.1, 1);
         }
 
         {
-            let mut final_pass =
-                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            let mut final_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("jfa final pass"),
+                timestamp_writes: profiler.compute_pass_timestamps("jfa final"),
+            });
             final_pass.set_pipeline(&self.final_pipeline);
             final_pass.set_bind_group(0, &self.main_bind_groups[1], &[]);
             final_pass.set_bind_group(1, &out_texture_bind_group, &[]);
@@ -1,17 +1,39 @@
-const nonexistent_coord: f32 = -2e9;
+// the lowest f16, the seed textures would turn anything lower into -inf
+const nonexistent_coord: f32 = -65504.;
+
+struct uniform_data {
+    // the part of the scene that changed since the last render, in pixels
+    dirty_min: vec2f,
+    dirty_max: vec2f,
+}
 
 @group(0) @binding(0)
 var in_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> uniforms: uniform_data;
+// the seeds of the last render, only read by partial updates
+@group(1) @binding(0)
+var old_seeds: texture_2d<f32>;
 @group(1) @binding(1)
-var out_texture: texture_storage_2d<rg16float, write>;
+var out_texture: texture_storage_2d<rgba16float, write>;
 
+// z is 1 for the pixels the other passes update, they keep their old seed otherwise
 @compute
 @workgroup_size(16, 16)
 fn main(@builtin(global_invocation_id) id: vec3u) {
+    let pixel_pos = vec2f(id.xy);
+    let old_seed = textureLoad(old_seeds, id.xy, 0).xy;
+    let dirty_dist = length(max(max(uniforms.dirty_min - pixel_pos, pixel_pos - uniforms.dirty_max), vec2f(0.)));
+    // nothing in the dirty rect can be closer than the old seed, with a pixel to spare for rounding
+    if old_seed.x != nonexistent_coord && distance(old_seed, pixel_pos) + 1. < dirty_dist {
+        textureStore(out_texture, id.xy, vec4f(old_seed, 0., 0.));
+        return;
+    }
+
     let color = textureLoad(in_texture, id.xy, 0);
     if color.a < 0.001 {
-        textureStore(out_texture, id.xy, vec4f(vec2f(nonexistent_coord), 0, 0));
+        textureStore(out_texture, id.xy, vec4f(vec2f(nonexistent_coord), 1., 0.));
     } else {
-        textureStore(out_texture, id.xy, vec4f(vec2f(id.xy), 0, 0));
+        textureStore(out_texture, id.xy, vec4f(vec2f(id.xy), 1., 0.));
     }
 }
@@ -1,22 +1,49 @@
+#![allow(non_snake_case)]
+
 mod brush;
+mod display;
 mod egui_renderer;
 mod gi;
+mod gpu;
+#[cfg(not(target_arch = "wasm32"))]
+mod headless;
 mod inpututil;
+mod inspector;
 mod jfa;
+mod profiler;
 mod scene;
 mod screenpass;
+mod session;
+mod shaders;
+mod textureio;
 mod texturerenderer;
+mod viewport;
+#[cfg(target_arch = "wasm32")]
+mod web;
 
+use display::Display;
 use egui_renderer::EguiRenderer;
 use gi::GI;
+use inspector::Inspector;
+use profiler::GpuProfiler;
 use scene::Scene;
-use texturerenderer::TextureRenderer;
+use shaders::ShaderWatcher;
+use viewport::Viewport;
 
 use egui_wgpu::wgpu;
 use winit::event::WindowEvent;
 
 use inpututil::InputController;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub use headless::render as render_headless;
+
+enum FileAction {
+    SaveOutput,
+    SaveSession,
+    LoadSession,
+}
+
 struct State<'a> {
     device: wgpu::Device,
     queue: wgpu::Queue,
@@ -27,12 +54,24 @@ struct State<'a> {
 
     input_controller: InputController,
 
-    texture_renderer: TextureRenderer,
+    display: Display,
     gi: GI,
     egui_renderer: EguiRenderer,
+    profiler: GpuProfiler,
+    inspector: Inspector,
 
     scene: Scene,
     out_texture: wgpu::Texture,
+    viewport: Viewport,
+    /// the gi output is out of date and has to be rendered again
+    gi_dirty: bool,
+    /// render gi every frame, even if nothing changed
+    continuous: bool,
+    /// only there with `--hot-reload`
+    shader_watcher: Option<ShaderWatcher>,
+
+    #[cfg(target_arch = "wasm32")]
+    session_picker: web::FilePicker,
 }
 
 impl<'a> State<'a> {
@@ -50,60 +89,49 @@ impl<'a> State<'a> {
             },
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT
                 | wgpu::TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::STORAGE_BINDING,
+                | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
             view_formats: &[],
         })
     }
 
-    async fn new(window: &'a winit::window::Window) -> Self {
+    async fn new(
+        window: &'a winit::window::Window,
+        adapter_options: &gpu::AdapterOptions,
+        hot_reload: bool,
+    ) -> Result<Self, String> {
         let size = window.inner_size();
-        let instance = wgpu::Instance::default();
-
-        let surface = instance.create_surface(window).unwrap();
-
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    required_features: wgpu::Features::PUSH_CONSTANTS
-                        | wgpu::Features::FLOAT32_FILTERABLE
-                        | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
-                    required_limits: wgpu::Limits {
-                        max_push_constant_size: 4,
-                        ..Default::default()
-                    },
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        let instance = adapter_options.create_instance();
+
+        let surface = instance
+            .create_surface(window)
+            .map_err(|e| format!("Couldn't create a surface: {}", e))?;
+
+        let adapter = gpu::request_adapter(&instance, adapter_options, Some(&surface)).await?;
+        log::info!("Running on {:?}", adapter.get_info());
+        window.set_title(&format!("RCR - {}", adapter.get_info().name));
+
+        let (device, queue) = gpu::request_device(&adapter).await?;
 
         let config = surface
             .get_default_config(&adapter, size.width, size.height)
-            .unwrap();
+            .ok_or("The surface isn't supported by the adapter")?;
         surface.configure(&device, &config);
 
-        let texture_renderer =
-            TextureRenderer::new(&device, wgpu::FilterMode::Linear, config.format);
+        let display = Display::new(&device, config.format);
         let gi = GI::new(&device, (size.width, size.height));
-        let egui_renderer = EguiRenderer::new(&device, config.format, &window);
+        let egui_renderer = EguiRenderer::new(&device, config.format, window);
+        let profiler = GpuProfiler::new(&device, &queue);
+        let inspector = Inspector::new(&device);
 
         let input_controller = InputController::default();
 
         let scene = Scene::new(&device, (size.width, size.height));
 
         let out_texture = State::create_out_texture(&device, (size.width, size.height));
+        let viewport = Viewport::new((size.width, size.height), (size.width, size.height));
 
-        State {
+        Ok(State {
             device,
             queue,
 
@@ -111,22 +139,34 @@ impl<'a> State<'a> {
             surface,
             config,
 
-            texture_renderer,
+            display,
             gi,
             egui_renderer,
+            profiler,
+            inspector,
 
             input_controller,
 
             scene,
             out_texture,
-        }
+            viewport,
+            gi_dirty: true,
+            continuous: false,
+            shader_watcher: hot_reload.then(ShaderWatcher::new),
+
+            #[cfg(target_arch = "wasm32")]
+            session_picker: web::FilePicker::default(),
+        })
     }
 
-    fn render_egui(&mut self, out_texture_view: &wgpu::TextureView) {
-        self.egui_renderer.render(
+    /// returns how long egui wants to wait before the next repaint
+    fn render_egui(&mut self, out_texture_view: &wgpu::TextureView) -> std::time::Duration {
+        let mut file_action = None;
+
+        let repaint_delay = self.egui_renderer.render(
             &self.device,
             &self.queue,
-            &self.window,
+            self.window,
             out_texture_view,
             egui_wgpu::ScreenDescriptor {
                 size_in_pixels: self.window.inner_size().into(),
@@ -135,13 +175,183 @@ impl<'a> State<'a> {
             |ctx| {
                 ctx.style_mut(|style| style.visuals.window_shadow = egui::epaint::Shadow::NONE);
 
+                self.gi.paint_overlay(ctx, &self.viewport);
                 self.scene.render_egui(ctx, &self.device, &self.queue);
-                self.gi.render_egui(&self.device, &self.queue, ctx);
+                self.gi_dirty |= self.gi.render_egui(&self.device, &self.queue, ctx);
+                self.display.render_egui(ctx);
+                self.profiler.render_egui(ctx, &mut self.continuous);
+                self.inspector.render_egui(ctx, &mut self.viewport);
+                if let Some(watcher) = &mut self.shader_watcher {
+                    watcher.render_egui(ctx);
+                }
+
+                egui::Window::new("File")
+                    .default_size(egui::Vec2::new(1., 1.))
+                    .show(ctx, |ui| {
+                        if ui.button("Save output").clicked() {
+                            file_action = Some(FileAction::SaveOutput);
+                        }
+                        ui.separator();
+                        if ui.button("Save session").clicked() {
+                            file_action = Some(FileAction::SaveSession);
+                        }
+                        if ui.button("Load session").clicked() {
+                            file_action = Some(FileAction::LoadSession);
+                        }
+                    });
             },
         );
+
+        let result = match file_action {
+            Some(FileAction::SaveOutput) => self.save_output(),
+            Some(FileAction::SaveSession) => self.save_session(),
+            Some(FileAction::LoadSession) => self.load_session(),
+            None => Ok(()),
+        };
+        if let Err(e) = result {
+            println!("{}", e);
+        }
+
+        if let Some(rect) = self.scene.take_dirty() {
+            self.gi.scene_changed(rect);
+            self.gi_dirty = true;
+        }
+        repaint_delay
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_output(&self) -> Result<(), String> {
+        match native_dialog::FileDialog::new()
+            .set_filename("output.png")
+            .add_filter("PNG image", &["png"])
+            .add_filter("OpenEXR image", &["exr"])
+            .show_save_single_file()
+            .unwrap()
+        {
+            Some(filename) => textureio::save_output(
+                &self.device,
+                &self.queue,
+                &self.out_texture,
+                &self.display,
+                &filename.into_os_string().into_string().unwrap(),
+            ),
+            None => Ok(()),
+        }
     }
 
-    fn render(&mut self) {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_session(&self) -> Result<(), String> {
+        match native_dialog::FileDialog::new()
+            .set_filename("session.ron")
+            .add_filter("RCR session", &["ron"])
+            .show_save_single_file()
+            .unwrap()
+        {
+            Some(filename) => session::save(
+                &self.device,
+                &self.queue,
+                &self.scene,
+                &self.gi,
+                &filename.into_os_string().into_string().unwrap(),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_session(&mut self) -> Result<(), String> {
+        match native_dialog::FileDialog::new()
+            .add_filter("RCR session", &["ron"])
+            .show_open_single_file()
+            .unwrap()
+        {
+            Some(filename) => {
+                self.gi_dirty = true;
+                session::load(
+                    &self.device,
+                    &self.queue,
+                    &mut self.scene,
+                    &mut self.gi,
+                    &filename.into_os_string().into_string().unwrap(),
+                )
+            }
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn save_output(&self) -> Result<(), String> {
+        let image = self
+            .display
+            .render_image(&self.device, &self.queue, &self.out_texture);
+        let size = (image.width(), image.height());
+        web::download_texture(&self.device, &self.queue, &image, move |data| {
+            Ok(("output.png".to_owned(), textureio::encode_png(size, data)?))
+        });
+        Ok(())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn save_session(&self) -> Result<(), String> {
+        let texture = self.scene.texture();
+        let size = (texture.width(), texture.height());
+        let gi_session = self.gi.session();
+        web::download_texture(&self.device, &self.queue, texture, move |data| {
+            let png = textureio::encode_png(size, data)?;
+            let text = session::to_ron(&png, gi_session)?;
+            Ok(("session.ron".to_owned(), text.into_bytes()))
+        });
+        Ok(())
+    }
+
+    /// the session is loaded in `load_picked_session` once the browser has read the file
+    #[cfg(target_arch = "wasm32")]
+    fn load_session(&mut self) -> Result<(), String> {
+        self.session_picker.open(".ron");
+        Ok(())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn load_picked_session(&mut self) -> Result<(), String> {
+        match self.session_picker.take() {
+            Some(bytes) => {
+                self.gi_dirty = true;
+                session::apply(
+                    &self.device,
+                    &self.queue,
+                    &mut self.scene,
+                    &mut self.gi,
+                    &String::from_utf8_lossy(&bytes),
+                )
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// applies this frame's zooming and panning, and follows the scene and window size
+    fn update_viewport(&mut self) -> Viewport {
+        self.viewport.scene_size = (self.scene.texture().width(), self.scene.texture().height());
+        self.viewport.window_size = (self.config.width, self.config.height);
+
+        let scroll = self.input_controller.get_scroll();
+        if scroll != 0. {
+            self.viewport.zoom_at(
+                self.input_controller.get_mouse_pos(),
+                f32::powf(1.25, scroll),
+            );
+        }
+        if self
+            .input_controller
+            .mouse_button_pressed(winit::event::MouseButton::Middle)
+        {
+            self.viewport
+                .pan_by(self.input_controller.get_mouse_delta());
+        }
+        self.viewport
+    }
+
+    /// returns how long to wait before the next frame, zero if it should follow right away
+    fn render(&mut self) -> std::time::Duration {
         let output = match self.surface.get_current_texture() {
             Ok(o) => o,
             Err(e) => {
@@ -149,105 +359,257 @@ impl<'a> State<'a> {
                     "Couldn't get current surface texture, skipping frame:\n{:?}",
                     e
                 );
-                return;
+                return std::time::Duration::ZERO;
             }
         };
 
+        #[cfg(target_arch = "wasm32")]
+        {
+            web::poll_downloads();
+            if let Err(e) = self.load_picked_session() {
+                log::error!("{}", e);
+            }
+        }
+
+        if let Some(watcher) = &mut self.shader_watcher {
+            if watcher.changed() {
+                watcher.set_result(shaders::join_errors([
+                    self.gi.reload_shaders(&self.device),
+                    self.scene.reload_shaders(&self.device),
+                ]));
+                self.gi_dirty = true;
+            }
+        }
+
+        let viewport = self.update_viewport();
+        self.profiler.begin_frame(&self.device);
+
         self.scene
-            .update(&self.device, &self.queue, &self.input_controller);
+            .update(&self.device, &self.queue, &self.input_controller, &viewport);
+        if let Some(rect) = self.scene.take_dirty() {
+            self.gi.scene_changed(rect);
+            self.gi_dirty = true;
+        }
+
+        if self.input_controller.ctrl_pressed()
+            && self
+                .input_controller
+                .mouse_button_just_pressed(winit::event::MouseButton::Left)
+        {
+            let pos = viewport.window_to_scene(self.input_controller.get_mouse_pos());
+            if viewport.contains_scene_pos(pos) {
+                self.gi_dirty |= self.gi.pick(pos);
+            }
+        }
+
+        // the scene can change size from the ui, gi follows it
+        if viewport.scene_size != (self.out_texture.width(), self.out_texture.height()) {
+            self.out_texture = State::create_out_texture(&self.device, viewport.scene_size);
+            self.gi.resize(&self.device, viewport.scene_size);
+            self.gi_dirty = true;
+        }
+
+        // the last output stays in out_texture while nothing changes
+        if self.gi_dirty {
+            self.gi.reset();
+        }
+        let rendered = self.continuous || self.gi_dirty || self.gi.converging();
+        if rendered {
+            self.gi.render(
+                &self.device,
+                &self.queue,
+                self.scene.texture(),
+                &self.out_texture,
+                &self.profiler,
+            );
+            self.gi_dirty = false;
+        }
 
-        self.gi.render(
+        let hovered = {
+            let pos = viewport.window_to_scene(self.input_controller.get_mouse_pos());
+            viewport
+                .contains_scene_pos(pos)
+                .then_some((pos.0 as u32, pos.1 as u32))
+        };
+        self.inspector.update(
             &self.device,
             &self.queue,
-            &self.scene.texture(),
+            hovered,
+            rendered,
             &self.out_texture,
+            self.scene.texture(),
+            self.gi.sdf_texture(),
         );
 
-        self.texture_renderer.render(
+        self.display.render(
             &self.device,
             &self.queue,
             &self.out_texture,
             &output.texture,
+            &viewport,
+            self.profiler.render_pass_timestamps("display"),
         );
+        self.profiler.end_frame(&self.device, &self.queue, rendered);
 
-        self.render_egui(&output.texture.create_view(&Default::default()));
+        let repaint_delay = self.render_egui(&output.texture.create_view(&Default::default()));
 
         output.present();
+
+        let needs_frame = self.continuous
+            || self.gi_dirty
+            || self.inspector.waiting()
+            || self.gi.waiting()
+            || self.gi.converging();
+        match (needs_frame, &self.shader_watcher) {
+            (true, _) => std::time::Duration::ZERO,
+            // keep waking up to look at the shader files
+            (false, Some(_)) => repaint_delay.min(ShaderWatcher::POLL_INTERVAL),
+            (false, None) => repaint_delay,
+        }
     }
 }
 
-pub async fn run() {
+const USAGE: &str = "\
+usage: RCR [options]
+       RCR render <scene image> <output image> [options]
+
+opens the editor, or renders a scene without a window, see RCR render --help
+
+options:
+    --hot-reload                    reload the shaders in src/ when they change";
+
+pub async fn run(args: &[String]) -> Result<(), String> {
+    let mut adapter_options = gpu::AdapterOptions::default();
+    let mut hot_reload = false;
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        if arg == "--help" || arg == "-h" {
+            println!("{}\n\n{}", USAGE, gpu::ADAPTER_USAGE);
+            return Ok(());
+        }
+        if arg == "--hot-reload" {
+            hot_reload = true;
+            continue;
+        }
+        if !adapter_options.parse_flag(arg, &mut args_iter)? {
+            return Err(format!(
+                "Unknown option: {}\n\n{}\n\n{}",
+                arg,
+                USAGE,
+                gpu::ADAPTER_USAGE
+            ));
+        }
+    }
+
+    if adapter_options.list_adapters {
+        let instance = adapter_options.create_instance();
+        println!("{}", gpu::adapter_list(&instance, adapter_options.backends));
+        return Ok(());
+    }
+
     let event_loop = winit::event_loop::EventLoop::new().unwrap();
-    event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
-    let window = winit::window::WindowBuilder::new()
-        .with_resizable(false)
-        .with_inner_size(winit::dpi::LogicalSize::new(1920, 1080))
-        .build(&event_loop)
-        .unwrap();
-
-    let mut state = State::new(&window).await;
-
-    event_loop
-        .run(move |event, target| match event {
-            winit::event::Event::WindowEvent {
+    event_loop.set_control_flow(winit::event_loop::ControlFlow::Wait);
+    let window_builder = winit::window::WindowBuilder::new()
+        .with_resizable(true)
+        .with_inner_size(winit::dpi::LogicalSize::new(1920, 1080));
+    #[cfg(target_arch = "wasm32")]
+    let window_builder = {
+        use winit::platform::web::WindowBuilderExtWebSys;
+        window_builder.with_append(true)
+    };
+    let window = window_builder.build(&event_loop).unwrap();
+
+    // the web event loop keeps running after this function returns
+    #[cfg(target_arch = "wasm32")]
+    let window: &'static winit::window::Window = Box::leak(Box::new(window));
+    #[cfg(not(target_arch = "wasm32"))]
+    let window = &window;
+
+    let mut state = State::new(window, &adapter_options, hot_reload).await?;
+
+    let event_handler =
+        move |event: winit::event::Event<()>,
+              target: &winit::event_loop::EventLoopWindowTarget<()>| {
+            // egui asked for a repaint after a delay, see RedrawRequested
+            if let winit::event::Event::NewEvents(winit::event::StartCause::ResumeTimeReached {
+                ..
+            }) = event
+            {
+                state.window.request_redraw();
+            }
+
+            if let winit::event::Event::WindowEvent {
                 ref event,
                 window_id: _,
-            } => {
+            } = event
+            {
+                // any input can change the ui or the scene, so it gets a new frame
+                if !matches!(event, WindowEvent::RedrawRequested) {
+                    state.window.request_redraw();
+                }
+
                 let consumed_by_egui = state
                     .egui_renderer
-                    .handle_input(&state.window, event)
+                    .handle_input(state.window, event)
                     .consumed;
                 if consumed_by_egui {
                     return;
                 }
 
-                let _consumed_by_ic = state.input_controller.process_event(&event);
+                let _consumed_by_ic = state.input_controller.process_event(event);
 
                 match event {
                     WindowEvent::Resized(new_size) => {
+                        if new_size.width == 0 || new_size.height == 0 {
+                            return;
+                        }
+
                         state.config.width = new_size.width;
                         state.config.height = new_size.height;
                         state.surface.configure(&state.device, &state.config);
-
-                        let ns = (new_size.width, new_size.height);
-
-                        state.out_texture = State::create_out_texture(&state.device, ns);
-                        state.gi.resize(&state.device, ns);
-
-                        // state.scene.resize(&state.device, ns);
-                        if state.scene.texture().width() != state.config.width
-                            || state.scene.texture().height() != state.config.height
-                        {
-                            state.scene.resize(&state.device, ns);
-                        }
                     }
                     WindowEvent::CloseRequested => target.exit(),
                     WindowEvent::RedrawRequested => {
+                        let start = web_time::Instant::now();
+                        let delay = state.render();
+                        state.profiler.push_cpu_frame_time(start.elapsed());
                         state.input_controller.init_frame();
-                        let start = std::time::Instant::now();
-                        state.render();
-                        println!("{:?}", std::time::Instant::now() - start);
-                        if state.scene.texture().width() != state.config.width
-                            || state.scene.texture().height() != state.config.height
-                        {
-                            let _ = state
-                                .window
-                                .request_inner_size(winit::dpi::LogicalSize::new(
-                                    state.scene.texture().width(),
-                                    state.scene.texture().height(),
-                                ));
+
+                        if delay.is_zero() {
+                            state.window.request_redraw();
                         }
-                        state.window.request_redraw();
+                        // winit builds the deadline itself, its Instant type is an older web-time's
+                        target.set_control_flow(match delay.is_zero() {
+                            true => winit::event_loop::ControlFlow::Wait,
+                            false => winit::event_loop::ControlFlow::wait_duration(delay),
+                        });
                     }
-                    WindowEvent::MouseInput {
-                        device_id: _,
-                        state: _,
-                        button: _,
-                    } => state.window.request_redraw(),
                     _ => {}
                 }
             }
-            _ => {}
-        })
-        .unwrap();
+        };
+
+    #[cfg(not(target_arch = "wasm32"))]
+    return event_loop
+        .run(event_handler)
+        .map_err(|e| format!("Event loop error: {}", e));
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        use winit::platform::web::EventLoopExtWebSys;
+        event_loop.spawn(event_handler);
+        Ok(())
+    }
+}
+
+/// entry point of the web build, draws into a canvas appended to the page
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(start)]
+pub async fn start() {
+    console_error_panic_hook::set_once();
+    console_log::init_with_level(log::Level::Warn).unwrap();
+
+    if let Err(e) = run(&[]).await {
+        log::error!("{}", e);
+    }
 }
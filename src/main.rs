@@ -1,6 +1,25 @@
-use RCR::run;
+#![allow(non_snake_case)]
 
+#[cfg(not(target_arch = "wasm32"))]
+use RCR::{render_headless, run};
+
+// the web build starts from RCR::start instead
+#[cfg(target_arch = "wasm32")]
+fn main() {}
+
+#[cfg(not(target_arch = "wasm32"))]
 fn main() {
     env_logger::init();
-    pollster::block_on(run());
+
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let result = if args.first().map(String::as_str) == Some("render") {
+        pollster::block_on(render_headless(&args[1..]))
+    } else {
+        pollster::block_on(run(&args))
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
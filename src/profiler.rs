@@ -0,0 +1,340 @@
+use egui_wgpu::wgpu;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// gpu-side buffers for timestamp queries, only exists if the adapter supports them
+struct Queries {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// set by the map callback once the readback buffer can be read
+    mapped: Arc<AtomicBool>,
+    /// set by the map callback instead if mapping failed, that readback is dropped
+    failed: Arc<AtomicBool>,
+    /// labels of the queries that are being read back, one per pass,
+    /// and whether that frame rendered gi
+    in_flight: Option<(Vec<String>, bool)>,
+}
+
+/// measures gpu time of individual passes with timestamp queries
+///
+/// passes ask for timestamp writes while recording, the results show up
+/// in the profiler window a few frames later
+pub struct GpuProfiler {
+    queries: Option<Queries>,
+    timestamp_period: f32,
+
+    recording: bool,
+    scope: RefCell<String>,
+    // labels of the passes recorded this frame, query i*2 and i*2+1 belong to pass i
+    frame_passes: RefCell<Vec<String>>,
+    // whether the last finished frame rendered gi, frames that only display the old output
+    // would drag the frame times down
+    gi_frame: bool,
+
+    pass_times: Vec<(String, VecDeque<f32>)>,
+    gpu_frame_times: VecDeque<f32>,
+    cpu_frame_times: VecDeque<f32>,
+}
+
+impl GpuProfiler {
+    const MAX_PASSES: u32 = 256;
+    const HISTORY_LEN: usize = 120;
+
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let queries = device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| {
+                let size = (GpuProfiler::MAX_PASSES * 2) as u64 * wgpu::QUERY_SIZE as u64;
+                Queries {
+                    query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                        label: Some("profiler query set"),
+                        ty: wgpu::QueryType::Timestamp,
+                        count: GpuProfiler::MAX_PASSES * 2,
+                    }),
+                    resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("profiler resolve buffer"),
+                        size,
+                        usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                        mapped_at_creation: false,
+                    }),
+                    readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("profiler readback buffer"),
+                        size,
+                        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                        mapped_at_creation: false,
+                    }),
+                    mapped: Arc::new(AtomicBool::new(false)),
+                    failed: Arc::new(AtomicBool::new(false)),
+                    in_flight: None,
+                }
+            });
+
+        GpuProfiler {
+            queries,
+            timestamp_period: queue.get_timestamp_period(),
+
+            recording: false,
+            scope: RefCell::new(String::new()),
+            frame_passes: RefCell::new(vec![]),
+            gi_frame: false,
+
+            pass_times: vec![],
+            gpu_frame_times: VecDeque::new(),
+            cpu_frame_times: VecDeque::new(),
+        }
+    }
+
+    pub fn supported(&self) -> bool {
+        self.queries.is_some()
+    }
+
+    /// prefix for the labels of the following passes, like the renderer's name
+    pub fn set_scope(&self, scope: &str) {
+        *self.scope.borrow_mut() = scope.to_owned();
+    }
+
+    /// returns the query indices for a new pass, if this frame is being recorded
+    fn allocate_pass(&self, label: &str) -> Option<(&wgpu::QuerySet, u32)> {
+        let queries = self.queries.as_ref().filter(|_| self.recording)?;
+        let mut passes = self.frame_passes.borrow_mut();
+        if passes.len() as u32 >= GpuProfiler::MAX_PASSES {
+            return None;
+        }
+
+        let scope = self.scope.borrow();
+        passes.push(match scope.is_empty() {
+            true => label.to_owned(),
+            false => format!("{} / {}", scope, label),
+        });
+        Some((&queries.query_set, (passes.len() as u32 - 1) * 2))
+    }
+
+    pub fn compute_pass_timestamps(
+        &self,
+        label: &str,
+    ) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        self.allocate_pass(label)
+            .map(|(query_set, index)| wgpu::ComputePassTimestampWrites {
+                query_set,
+                beginning_of_pass_write_index: Some(index),
+                end_of_pass_write_index: Some(index + 1),
+            })
+    }
+
+    pub fn render_pass_timestamps(
+        &self,
+        label: &str,
+    ) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        self.allocate_pass(label)
+            .map(|(query_set, index)| wgpu::RenderPassTimestampWrites {
+                query_set,
+                beginning_of_pass_write_index: Some(index),
+                end_of_pass_write_index: Some(index + 1),
+            })
+    }
+
+    fn push_sample(history: &mut VecDeque<f32>, value: f32) {
+        if history.len() >= GpuProfiler::HISTORY_LEN {
+            history.pop_front();
+        }
+        history.push_back(value);
+    }
+
+    /// collects finished readbacks, must be called before recording any passes
+    pub fn begin_frame(&mut self, device: &wgpu::Device) {
+        self.scope.borrow_mut().clear();
+        self.frame_passes.borrow_mut().clear();
+
+        let Some(queries) = self.queries.as_mut() else {
+            return;
+        };
+
+        device.poll(wgpu::Maintain::Poll);
+        if queries.failed.swap(false, Ordering::AcqRel) {
+            queries.in_flight = None;
+        }
+        if queries.mapped.swap(false, Ordering::AcqRel) {
+            let (labels, gi_frame) = queries.in_flight.take().unwrap();
+            let timestamps = {
+                let slice = queries.readback_buffer.slice(..);
+                let data = slice.get_mapped_range();
+                bytemuck::cast_slice::<u8, u64>(&data)[..labels.len() * 2].to_vec()
+            };
+            queries.readback_buffer.unmap();
+
+            // passes with the same label in one frame are summed up, like the jfa steps
+            let mut frame: Vec<(String, f32)> = vec![];
+            for (i, label) in labels.into_iter().enumerate() {
+                let ticks = timestamps[i * 2 + 1].saturating_sub(timestamps[i * 2]);
+                let ms = ticks as f32 * self.timestamp_period / 1_000_000.;
+                match frame.iter_mut().find(|(l, _)| *l == label) {
+                    Some((_, total)) => *total += ms,
+                    None => frame.push((label, ms)),
+                }
+            }
+
+            // passes that didn't run this frame keep their history
+            for (label, ms) in &frame {
+                match self.pass_times.iter_mut().find(|(l, _)| l == label) {
+                    Some((_, history)) => GpuProfiler::push_sample(history, *ms),
+                    None => self.pass_times.push((label.clone(), VecDeque::from([*ms]))),
+                }
+            }
+
+            if gi_frame {
+                let total = frame.iter().map(|(_, ms)| ms).sum();
+                GpuProfiler::push_sample(&mut self.gpu_frame_times, total);
+            }
+        }
+
+        self.recording = queries.in_flight.is_none();
+    }
+
+    /// resolves the queries recorded this frame and starts reading them back
+    ///
+    /// `gi_frame` tells if gi was rendered, only those frames go into the frame times
+    pub fn end_frame(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, gi_frame: bool) {
+        self.gi_frame = gi_frame;
+
+        let Some(queries) = self.queries.as_mut().filter(|_| self.recording) else {
+            return;
+        };
+        self.recording = false;
+
+        let labels = std::mem::take(&mut *self.frame_passes.borrow_mut());
+        if labels.is_empty() {
+            return;
+        }
+        let num_queries = labels.len() as u32 * 2;
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("profiler resolve encoder"),
+        });
+        encoder.resolve_query_set(
+            &queries.query_set,
+            0..num_queries,
+            &queries.resolve_buffer,
+            0,
+        );
+        encoder.copy_buffer_to_buffer(
+            &queries.resolve_buffer,
+            0,
+            &queries.readback_buffer,
+            0,
+            num_queries as u64 * wgpu::QUERY_SIZE as u64,
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let mapped = queries.mapped.clone();
+        let failed = queries.failed.clone();
+        queries.readback_buffer.slice(..).map_async(
+            wgpu::MapMode::Read,
+            move |result| match result {
+                Ok(()) => mapped.store(true, Ordering::Release),
+                Err(e) => {
+                    println!("Error reading back timestamps: {}", e);
+                    failed.store(true, Ordering::Release);
+                }
+            },
+        );
+        queries.in_flight = Some((labels, gi_frame));
+    }
+
+    /// ignored if the frame didn't render gi, see `end_frame`
+    pub fn push_cpu_frame_time(&mut self, duration: std::time::Duration) {
+        if !self.gi_frame {
+            return;
+        }
+        GpuProfiler::push_sample(&mut self.cpu_frame_times, duration.as_secs_f32() * 1000.);
+    }
+
+    fn average(history: &VecDeque<f32>) -> f32 {
+        history.iter().sum::<f32>() / history.len().max(1) as f32
+    }
+
+    fn plot_frame_times(&self, ui: &mut egui::Ui) {
+        let (rect, _) = ui.allocate_exact_size(egui::Vec2::new(300., 80.), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0., egui::Color32::from_gray(20));
+
+        let max_ms = self
+            .gpu_frame_times
+            .iter()
+            .chain(self.cpu_frame_times.iter())
+            .fold(1f32, |a, &b| a.max(b));
+
+        let line = |history: &VecDeque<f32>, color: egui::Color32| {
+            let points = history
+                .iter()
+                .enumerate()
+                .map(|(i, ms)| {
+                    egui::Pos2::new(
+                        rect.left() + rect.width() * i as f32 / GpuProfiler::HISTORY_LEN as f32,
+                        rect.bottom() - rect.height() * ms / max_ms,
+                    )
+                })
+                .collect();
+            painter.add(egui::Shape::line(points, egui::Stroke::new(1., color)));
+        };
+        line(
+            &self.cpu_frame_times,
+            egui::Color32::from_rgb(150, 150, 150),
+        );
+        line(
+            &self.gpu_frame_times,
+            egui::Color32::from_rgb(100, 200, 100),
+        );
+
+        painter.text(
+            rect.left_top(),
+            egui::Align2::LEFT_TOP,
+            format!("{:.2} ms", max_ms),
+            egui::FontId::monospace(10.),
+            egui::Color32::from_gray(200),
+        );
+    }
+
+    /// `continuous` is the app's toggle for rendering every frame, so timings keep coming in
+    pub fn render_egui(&mut self, ctx: &egui::Context, continuous: &mut bool) {
+        egui::Window::new("Profiler")
+            .default_open(false)
+            .show(ctx, |ui| {
+                ui.checkbox(continuous, "Render continuously");
+
+                ui.label(format!(
+                    "CPU frame: {:.2} ms",
+                    GpuProfiler::average(&self.cpu_frame_times)
+                ));
+
+                if !self.supported() {
+                    ui.colored_label(
+                        egui::Color32::from_rgb(150, 150, 150),
+                        "Timestamp queries are not supported by this adapter,\nonly CPU time is measured",
+                    );
+                    self.plot_frame_times(ui);
+                    return;
+                }
+
+                ui.label(format!(
+                    "GPU frame: {:.2} ms",
+                    GpuProfiler::average(&self.gpu_frame_times)
+                ));
+                self.plot_frame_times(ui);
+
+                ui.heading("Passes");
+                egui::Grid::new("profiler passes")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (label, history) in &self.pass_times {
+                            ui.label(label);
+                            ui.monospace(format!("{:7.3} ms", GpuProfiler::average(history)));
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+}
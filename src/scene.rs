@@ -1,13 +1,56 @@
 use crate::brush::Brush;
 use crate::brush::BrushShape;
+use crate::textureio;
+use crate::texturerenderer::TextureRenderer;
+use crate::viewport::Viewport;
+#[cfg(target_arch = "wasm32")]
+use crate::web;
 use crate::InputController;
 use egui_wgpu::wgpu;
 
+/// what happens to the painted scene when its size changes
+#[derive(Clone, Copy, PartialEq)]
+pub enum ResizeMode {
+    /// keep the top left corner in place, crop or pad with empty space
+    Crop,
+    /// same as crop, but keep the center in place
+    CropCentered,
+    /// stretch the old scene over the new size
+    Resample,
+}
+
+/// the part of the scene that changed, in pixels, `max` is exclusive
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DirtyRect {
+    pub min: (u32, u32),
+    pub max: (u32, u32),
+}
+
+impl DirtyRect {
+    /// the whole scene, whatever its size
+    pub const ALL: DirtyRect = DirtyRect {
+        min: (0, 0),
+        max: (u32::MAX, u32::MAX),
+    };
+
+    pub fn union(self, other: DirtyRect) -> DirtyRect {
+        DirtyRect {
+            min: (self.min.0.min(other.min.0), self.min.1.min(other.min.1)),
+            max: (self.max.0.max(other.max.0), self.max.1.max(other.max.1)),
+        }
+    }
+
+    pub fn covers(&self, size: (u32, u32)) -> bool {
+        self.min == (0, 0) && self.max.0 >= size.0 && self.max.1 >= size.1
+    }
+}
+
 struct SceneConfig {
     brush_shape: BrushShape,
     brush_color_left: [f32; 3],
     brush_color_right: [f32; 3],
     brush_size: u32,
+    resize_mode: ResizeMode,
 }
 
 impl Default for SceneConfig {
@@ -17,6 +60,7 @@ impl Default for SceneConfig {
             brush_color_left: [1., 1., 1.],
             brush_color_right: [0., 0., 0.],
             brush_size: 30,
+            resize_mode: ResizeMode::Crop,
         }
     }
 }
@@ -24,8 +68,15 @@ impl Default for SceneConfig {
 pub struct Scene {
     config: SceneConfig,
     brush: Brush,
+    resampler: TextureRenderer,
     texture: wgpu::Texture,
     texture_view: wgpu::TextureView,
+
+    gui_resolution: (u32, u32),
+    // what changed since the last take_dirty, so gi knows to render again
+    dirty: Option<DirtyRect>,
+    #[cfg(target_arch = "wasm32")]
+    picker: web::FilePicker,
 }
 
 impl Scene {
@@ -43,7 +94,9 @@ impl Scene {
             },
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT
                 | wgpu::TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::STORAGE_BINDING,
+                | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
             view_formats: &[],
         })
     }
@@ -51,6 +104,11 @@ impl Scene {
     pub fn new(device: &wgpu::Device, texture_size: (u32, u32)) -> Self {
         let config = SceneConfig::default();
         let brush = Brush::new(device);
+        let resampler = TextureRenderer::new(
+            device,
+            wgpu::FilterMode::Nearest,
+            wgpu::TextureFormat::Rgba8Unorm,
+        );
         let texture = Scene::create_texture(device, texture_size);
 
         let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
@@ -58,97 +116,152 @@ impl Scene {
         Scene {
             config,
             brush,
+            resampler,
             texture,
             texture_view,
+
+            gui_resolution: texture_size,
+            dirty: Some(DirtyRect::ALL),
+            #[cfg(target_arch = "wasm32")]
+            picker: web::FilePicker::default(),
         }
     }
 
-    pub fn resize(&mut self, device: &wgpu::Device, texture_size: (u32, u32)) {
-        self.texture = Scene::create_texture(device, texture_size);
+    /// resizes the scene texture, keeping the painted content according to the resize mode
+    pub fn resize(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, texture_size: (u32, u32)) {
+        self.gui_resolution = texture_size;
+        self.dirty = Some(DirtyRect::ALL);
+        let old_texture = std::mem::replace(
+            &mut self.texture,
+            Scene::create_texture(device, texture_size),
+        );
         self.texture_view = self
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
+
+        match self.config.resize_mode {
+            ResizeMode::Crop | ResizeMode::CropCentered => {
+                let copy_size = (
+                    u32::min(old_texture.width(), texture_size.0),
+                    u32::min(old_texture.height(), texture_size.1),
+                );
+                let (src_origin, dst_origin) = if self.config.resize_mode == ResizeMode::Crop {
+                    (wgpu::Origin3d::ZERO, wgpu::Origin3d::ZERO)
+                } else {
+                    let centered = |size: (u32, u32)| wgpu::Origin3d {
+                        x: (size.0 - copy_size.0) / 2,
+                        y: (size.1 - copy_size.1) / 2,
+                        z: 0,
+                    };
+                    (
+                        centered((old_texture.width(), old_texture.height())),
+                        centered(texture_size),
+                    )
+                };
+
+                let mut encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+                encoder.copy_texture_to_texture(
+                    wgpu::ImageCopyTexture {
+                        origin: src_origin,
+                        ..old_texture.as_image_copy()
+                    },
+                    wgpu::ImageCopyTexture {
+                        origin: dst_origin,
+                        ..self.texture.as_image_copy()
+                    },
+                    wgpu::Extent3d {
+                        width: copy_size.0,
+                        height: copy_size.1,
+                        depth_or_array_layers: 1,
+                    },
+                );
+                queue.submit(Some(encoder.finish()));
+            }
+            ResizeMode::Resample => {
+                self.resampler
+                    .render(device, queue, &old_texture, &self.texture);
+            }
+        }
     }
 
     fn clear_texture(&mut self, device: &wgpu::Device) {
         self.texture = Scene::create_texture(device, (self.texture.width(), self.texture.height()));
+        self.dirty = Some(DirtyRect::ALL);
         self.texture_view = self
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
     fn load_texture_from_file(
         &mut self,
         filename: String,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
     ) {
-        let texture_image = match image::load(
-            std::io::BufReader::new(match std::fs::File::open(filename) {
-                Ok(f) => f,
-                Err(e) => {
-                    println!("Error opening file: {}", e);
-                    return;
-                }
-            }),
-            image::ImageFormat::Png,
-        ) {
-            Ok(img) => img,
+        let texture = match textureio::load_texture(device, queue, &filename, "scene texture") {
+            Ok(t) => t,
             Err(e) => {
-                println!("Error loading image: {}", e);
+                println!("{}", e);
                 return;
             }
         };
 
-        let texture_rgba = texture_image.to_rgba8();
-        let dimensions = texture_rgba.dimensions();
+        self.gui_resolution = (texture.width(), texture.height());
+        self.texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.texture = texture;
+        self.dirty = Some(DirtyRect::ALL);
+    }
 
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("scene texture"),
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            mip_level_count: 1,
-            sample_count: 1,
-            size: wgpu::Extent3d {
-                width: dimensions.0,
-                height: dimensions.1,
-                depth_or_array_layers: 1,
-            },
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
-                | wgpu::TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::COPY_DST
-                | wgpu::TextureUsages::STORAGE_BINDING,
-            view_formats: &[],
-        });
-
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &texture_rgba,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
-            },
-            wgpu::Extent3d {
-                width: dimensions.0,
-                height: dimensions.1,
-                depth_or_array_layers: 1,
-            },
-        );
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn encode_png(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Vec<u8>, String> {
+        textureio::encode_texture_png(device, queue, &self.texture)
+    }
 
+    pub fn load_png(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        png: &[u8],
+    ) -> Result<(), String> {
+        let texture = textureio::load_texture_from_memory(device, queue, png, "scene texture")?;
+        self.gui_resolution = (texture.width(), texture.height());
         self.texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         self.texture = texture;
+        self.dirty = Some(DirtyRect::ALL);
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_texture_to_file(&self, filename: String, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if let Err(e) = textureio::save_texture(device, queue, &self.texture, &filename) {
+            println!("{}", e);
+        }
+    }
+
+    pub fn reload_shaders(&mut self, device: &wgpu::Device) -> Result<(), String> {
+        self.brush.reload_shaders(device)
+    }
+
+    fn mark_dirty(&mut self, rect: DirtyRect) {
+        self.dirty = Some(self.dirty.map_or(rect, |dirty| dirty.union(rect)));
+    }
+
+    /// the part of the scene that changed since the last call
+    pub fn take_dirty(&mut self) -> Option<DirtyRect> {
+        self.dirty.take()
     }
 
     pub fn texture(&self) -> &wgpu::Texture {
         &self.texture
     }
 
+    #[allow(dead_code)]
     pub fn texture_view(&self) -> &wgpu::TextureView {
         &self.texture_view
     }
@@ -158,14 +271,29 @@ impl Scene {
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         input_controller: &InputController,
+        viewport: &Viewport,
     ) {
         if input_controller.key_just_pressed(winit::keyboard::KeyCode::Space) {
             self.clear_texture(device);
         }
 
-        let mouse_pos = {
-            let mpf = input_controller.get_mouse_pos();
-            [mpf.0 as u32, mpf.1 as u32]
+        let scene_pos = viewport.window_to_scene(input_controller.get_mouse_pos());
+        // ctrl+click picks in the gi renderers instead of painting
+        if !viewport.contains_scene_pos(scene_pos) || input_controller.ctrl_pressed() {
+            return;
+        }
+        let mouse_pos = [scene_pos.0 as u32, scene_pos.1 as u32];
+        // covers both brush shapes
+        let size = self.config.brush_size;
+        let stroke = DirtyRect {
+            min: (
+                mouse_pos[0].saturating_sub(size),
+                mouse_pos[1].saturating_sub(size),
+            ),
+            max: (
+                mouse_pos[0].saturating_add(size),
+                mouse_pos[1].saturating_add(size),
+            ),
         };
 
         if input_controller.mouse_button_pressed(winit::event::MouseButton::Left) {
@@ -178,6 +306,7 @@ impl Scene {
                 self.config.brush_size,
                 self.config.brush_color_left,
             );
+            self.mark_dirty(stroke);
         }
 
         if input_controller.mouse_button_pressed(winit::event::MouseButton::Right) {
@@ -190,10 +319,18 @@ impl Scene {
                 self.config.brush_size,
                 self.config.brush_color_right,
             );
+            self.mark_dirty(stroke);
         }
     }
 
     pub fn render_egui(&mut self, ctx: &egui::Context, device: &wgpu::Device, queue: &wgpu::Queue) {
+        #[cfg(target_arch = "wasm32")]
+        if let Some(bytes) = self.picker.take() {
+            if let Err(e) = self.load_png(device, queue, &bytes) {
+                log::error!("{}", e);
+            }
+        }
+
         egui::Window::new("Scene")
             .default_size(egui::Vec2::new(1., 1.))
             .show(ctx, |ui| {
@@ -223,11 +360,35 @@ impl Scene {
                 ui.heading("Brush RMB color");
                 ui.color_edit_button_rgb(&mut self.config.brush_color_right);
 
+                ui.heading("Resolution");
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut self.gui_resolution.0).range(1..=8192));
+                    ui.label("x");
+                    ui.add(egui::DragValue::new(&mut self.gui_resolution.1).range(1..=8192));
+                    if ui.button("Apply").clicked() {
+                        self.resize(device, queue, self.gui_resolution);
+                    }
+                });
+
+                ui.heading("On resize");
+                ui.radio_value(&mut self.config.resize_mode, ResizeMode::Crop, "Crop / pad");
+                ui.radio_value(
+                    &mut self.config.resize_mode,
+                    ResizeMode::CropCentered,
+                    "Crop / pad, centered",
+                );
+                ui.radio_value(
+                    &mut self.config.resize_mode,
+                    ResizeMode::Resample,
+                    "Resample",
+                );
+
                 if ui.button("Clear Scene").clicked() {
-                    self.clear_texture(&device);
+                    self.clear_texture(device);
                 }
 
                 if ui.button("Load scene from file").clicked() {
+                    #[cfg(not(target_arch = "wasm32"))]
                     if let Some(filename) = native_dialog::FileDialog::new()
                         .show_open_single_file()
                         .unwrap()
@@ -238,6 +399,31 @@ impl Scene {
                             queue,
                         );
                     }
+                    #[cfg(target_arch = "wasm32")]
+                    self.picker.open("image/*");
+                }
+
+                if ui.button("Save scene to file").clicked() {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if let Some(filename) = native_dialog::FileDialog::new()
+                        .set_filename("scene.png")
+                        .add_filter("PNG image", &["png"])
+                        .show_save_single_file()
+                        .unwrap()
+                    {
+                        self.save_texture_to_file(
+                            filename.into_os_string().into_string().unwrap(),
+                            device,
+                            queue,
+                        );
+                    }
+                    #[cfg(target_arch = "wasm32")]
+                    {
+                        let size = (self.texture.width(), self.texture.height());
+                        web::download_texture(device, queue, &self.texture, move |data| {
+                            Ok(("scene.png".to_owned(), textureio::encode_png(size, data)?))
+                        });
+                    }
                 }
             });
     }
@@ -128,9 +128,13 @@ impl ScreenPass {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some(&(self.label.clone() + " render pass")),
                 color_attachments: desc.color_attachments,
+                timestamp_writes: desc.timestamp_writes.clone(),
                 ..Default::default()
             });
 
+            if let Some([x, y, w, h]) = desc.viewport {
+                render_pass.set_viewport(x, y, w, h, 0., 1.);
+            }
             render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(0, &bind_group, &[]);
             render_pass.draw(0..4, 0..1);
@@ -146,4 +150,7 @@ pub struct ScreenPassRenderDescriptor<'a> {
     pub queue: &'a wgpu::Queue,
     pub bind_group_resources: &'a [wgpu::BindingResource<'a>],
     pub color_attachments: &'a [Option<wgpu::RenderPassColorAttachment<'a>>],
+    /// [x, y, width, height] in pixels, the whole target if none
+    pub viewport: Option<[f32; 4]>,
+    pub timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'a>>,
 }
@@ -0,0 +1,73 @@
+use crate::gi::{GISession, GI};
+use crate::scene::Scene;
+use crate::textureio;
+use base64::Engine;
+use egui_wgpu::wgpu;
+
+/// everything in the workspace, saved as one ron file
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Session {
+    /// base64 encoded png
+    scene: String,
+    gi: GISession,
+}
+
+/// serializes a session, `scene_png` is the scene texture encoded as png
+pub fn to_ron(scene_png: &[u8], gi: GISession) -> Result<String, String> {
+    let session = Session {
+        scene: base64::engine::general_purpose::STANDARD.encode(scene_png),
+        gi,
+    };
+
+    ron::ser::to_string_pretty(&session, ron::ser::PrettyConfig::default())
+        .map_err(|e| format!("Error serializing session: {}", e))
+}
+
+/// replaces the scene and renderers with the ones from a serialized session
+pub fn apply(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    scene: &mut Scene,
+    gi: &mut GI,
+    text: &str,
+) -> Result<(), String> {
+    let session: Session =
+        ron::from_str(text).map_err(|e| format!("Error parsing session: {}", e))?;
+
+    let png = base64::engine::general_purpose::STANDARD
+        .decode(session.scene)
+        .map_err(|e| format!("Error decoding session scene: {}", e))?;
+
+    // nothing is replaced until the whole session checks out
+    let scene_size = textureio::image_dimensions(&png)?;
+    GI::check_session(device, &session.gi, scene_size)?;
+
+    scene.load_png(device, queue, &png)?;
+    gi.load_session(device, queue, session.gi, scene_size);
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    scene: &Scene,
+    gi: &GI,
+    filename: &str,
+) -> Result<(), String> {
+    let text = to_ron(&scene.encode_png(device, queue)?, gi.session())?;
+    std::fs::write(filename, text).map_err(|e| format!("Error writing session: {}", e))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    scene: &mut Scene,
+    gi: &mut GI,
+    filename: &str,
+) -> Result<(), String> {
+    let text =
+        std::fs::read_to_string(filename).map_err(|e| format!("Error reading session: {}", e))?;
+    apply(device, queue, scene, gi, &text)
+}
@@ -0,0 +1,297 @@
+// shaders that can be reloaded from disk while the app runs, see `--hot-reload`
+
+use egui_wgpu::wgpu;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use web_time::Instant;
+
+/// a wgsl file in the source tree, `path` is relative to the crate root
+#[derive(Clone, Copy)]
+pub struct ShaderFile {
+    pub path: &'static str,
+    embedded: &'static str,
+}
+
+macro_rules! shader_file {
+    ($path:literal) => {
+        crate::shaders::ShaderFile::new(
+            $path,
+            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $path)),
+        )
+    };
+}
+pub(crate) use shader_file;
+
+thread_local! {
+    // the last source of every reloaded file that compiled, new pipelines are made from these
+    static RELOADED: RefCell<HashMap<&'static str, String>> = RefCell::new(HashMap::new());
+}
+
+impl ShaderFile {
+    pub const fn new(path: &'static str, embedded: &'static str) -> Self {
+        ShaderFile { path, embedded }
+    }
+
+    fn disk_path(&self) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(self.path)
+    }
+
+    /// the source pipelines are made from, the embedded one until a reload succeeds
+    pub fn source(&self) -> String {
+        RELOADED
+            .with_borrow(|reloaded| reloaded.get(self.path).cloned())
+            .unwrap_or_else(|| self.embedded.to_owned())
+    }
+}
+
+/// a compute pipeline that can be rebuilt from its shader file, derefs to the wgpu pipeline
+pub struct ComputePipeline {
+    label: &'static str,
+    shader: ShaderFile,
+    // prepended to the file, for declarations that depend on the device or settings
+    prefix: String,
+    entry_point: &'static str,
+    layout: wgpu::PipelineLayout,
+
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl ComputePipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        label: &'static str,
+        shader: ShaderFile,
+        layout: wgpu::PipelineLayout,
+    ) -> Self {
+        ComputePipeline::with_prefix(device, label, shader, "", layout)
+    }
+
+    pub fn with_prefix(
+        device: &wgpu::Device,
+        label: &'static str,
+        shader: ShaderFile,
+        prefix: impl Into<String>,
+        layout: wgpu::PipelineLayout,
+    ) -> Self {
+        ComputePipeline::with_entry_point(device, label, shader, prefix, "main", layout)
+    }
+
+    /// for files with more than one entry point, the other constructors use `main`
+    pub fn with_entry_point(
+        device: &wgpu::Device,
+        label: &'static str,
+        shader: ShaderFile,
+        prefix: impl Into<String>,
+        entry_point: &'static str,
+        layout: wgpu::PipelineLayout,
+    ) -> Self {
+        let prefix = prefix.into();
+        let pipeline = ComputePipeline::create(
+            device,
+            label,
+            &prefix,
+            entry_point,
+            &shader.source(),
+            &layout,
+        );
+        ComputePipeline {
+            label,
+            shader,
+            prefix,
+            entry_point,
+            layout,
+
+            pipeline,
+        }
+    }
+
+    fn create(
+        device: &wgpu::Device,
+        label: &str,
+        prefix: &str,
+        entry_point: &str,
+        source: &str,
+        layout: &wgpu::PipelineLayout,
+    ) -> wgpu::ComputePipeline {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl([prefix, source].concat().into()),
+        });
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            module: &module,
+            entry_point,
+            compilation_options: Default::default(),
+        })
+    }
+
+    /// rebuilds the pipeline from the file on disk, keeps the old one if that fails
+    pub fn reload(&mut self, device: &wgpu::Device) -> Result<(), String> {
+        let source = std::fs::read_to_string(self.shader.disk_path())
+            .map_err(|e| format!("Error reading {}: {}", self.shader.path, e))?;
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = ComputePipeline::create(
+            device,
+            self.label,
+            &self.prefix,
+            self.entry_point,
+            &source,
+            &self.layout,
+        );
+        if let Some(e) = pop_error_scope(device) {
+            return Err(format!("{} ({}):\n{}", self.shader.path, self.label, e));
+        }
+
+        self.pipeline = pipeline;
+        RELOADED.with_borrow_mut(|reloaded| reloaded.insert(self.shader.path, source));
+        Ok(())
+    }
+}
+
+impl std::ops::Deref for ComputePipeline {
+    type Target = wgpu::ComputePipeline;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pipeline
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn pop_error_scope(device: &wgpu::Device) -> Option<wgpu::Error> {
+    pollster::block_on(device.pop_error_scope())
+}
+
+// there's nothing to reload from in the browser, and nothing to block on either,
+// so the error only ends up in the log
+#[cfg(target_arch = "wasm32")]
+fn pop_error_scope(device: &wgpu::Device) -> Option<wgpu::Error> {
+    let error = device.pop_error_scope();
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Some(e) = error.await {
+            log::error!("{}", e);
+        }
+    });
+    None
+}
+
+/// turns the results of several reloads into one, keeping every error
+pub fn join_errors(results: impl IntoIterator<Item = Result<(), String>>) -> Result<(), String> {
+    let mut errors: Vec<String> = vec![];
+    for e in results.into_iter().filter_map(Result::err) {
+        // renderers share shaders, so the same error can show up more than once
+        if !errors.contains(&e) {
+            errors.push(e);
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors.join("\n\n")),
+    }
+}
+
+/// watches the wgsl files in the source tree for changes
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    modified: Vec<(PathBuf, SystemTime)>,
+    last_scan: Instant,
+    reload_requested: bool,
+    /// the result of the last reload
+    result: Result<(), String>,
+}
+
+impl ShaderWatcher {
+    /// how often the files are checked, frames in between don't touch the disk
+    pub const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+    pub fn new() -> Self {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src");
+        let modified = ShaderWatcher::scan(&dir);
+        ShaderWatcher {
+            dir,
+            modified,
+            last_scan: Instant::now(),
+            reload_requested: false,
+            result: Ok(()),
+        }
+    }
+
+    fn scan(dir: &std::path::Path) -> Vec<(PathBuf, SystemTime)> {
+        let mut files = vec![];
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return files;
+        };
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.is_dir() {
+                files.extend(ShaderWatcher::scan(&path));
+            } else if path.extension().is_some_and(|ext| ext == "wgsl") {
+                if let Ok(modified) = path.metadata().and_then(|m| m.modified()) {
+                    files.push((path, modified));
+                }
+            }
+        }
+        files.sort();
+        files
+    }
+
+    /// whether a shader changed since the last call, or a reload was asked for
+    pub fn changed(&mut self) -> bool {
+        if self.reload_requested {
+            self.reload_requested = false;
+            return true;
+        }
+        if self.last_scan.elapsed() < ShaderWatcher::POLL_INTERVAL {
+            return false;
+        }
+
+        self.last_scan = Instant::now();
+        let modified = ShaderWatcher::scan(&self.dir);
+        let changed = modified != self.modified;
+        self.modified = modified;
+        changed
+    }
+
+    pub fn set_result(&mut self, result: Result<(), String>) {
+        if let Err(e) = &result {
+            log::error!("Shader reload failed:\n{}", e);
+        }
+        self.result = result;
+    }
+
+    pub fn render_egui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Shaders")
+            .default_size(egui::Vec2::new(1., 1.))
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Watching {} files in {}",
+                    self.modified.len(),
+                    self.dir.display()
+                ));
+                if ui.button("Reload").clicked() {
+                    self.reload_requested = true;
+                }
+                ui.separator();
+
+                match &self.result {
+                    Ok(()) => {
+                        ui.colored_label(
+                            egui::Color32::from_rgb(0, 200, 0),
+                            "All shaders compiled",
+                        );
+                    }
+                    Err(e) => {
+                        ui.colored_label(
+                            egui::Color32::from_rgb(255, 0, 0),
+                            "Errors, the previous pipelines are kept:",
+                        );
+                        egui::ScrollArea::vertical()
+                            .max_height(400.)
+                            .show(ui, |ui| ui.monospace(e));
+                    }
+                }
+            });
+    }
+}
@@ -0,0 +1,253 @@
+use egui_wgpu::wgpu;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// loads an image file into an Rgba8Unorm texture usable as a scene
+///
+/// the format is detected from the file contents
+pub fn load_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    filename: &str,
+    label: &str,
+) -> Result<wgpu::Texture, String> {
+    let bytes = std::fs::read(filename).map_err(|e| format!("Error opening file: {}", e))?;
+    load_texture_from_memory(device, queue, &bytes, label)
+}
+
+/// the size of an encoded image, read from its header
+pub fn image_dimensions(bytes: &[u8]) -> Result<(u32, u32), String> {
+    image::ImageReader::new(std::io::Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| format!("Error loading image: {}", e))?
+        .into_dimensions()
+        .map_err(|e| format!("Error loading image: {}", e))
+}
+
+/// same as `load_texture`, but for an image that's already in memory
+pub fn load_texture_from_memory(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    bytes: &[u8],
+    label: &str,
+) -> Result<wgpu::Texture, String> {
+    let texture_image = image::guess_format(bytes)
+        .and_then(|format| image::load_from_memory_with_format(bytes, format))
+        .map_err(|e| format!("Error loading image: {}", e))?;
+
+    let texture_rgba = texture_image.to_rgba8();
+    let dimensions = texture_rgba.dimensions();
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        mip_level_count: 1,
+        sample_count: 1,
+        size: wgpu::Extent3d {
+            width: dimensions.0,
+            height: dimensions.1,
+            depth_or_array_layers: 1,
+        },
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::COPY_SRC
+            | wgpu::TextureUsages::STORAGE_BINDING,
+        view_formats: &[],
+    });
+
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        &texture_rgba,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * dimensions.0),
+            rows_per_image: Some(dimensions.1),
+        },
+        wgpu::Extent3d {
+            width: dimensions.0,
+            height: dimensions.1,
+            depth_or_array_layers: 1,
+        },
+    );
+
+    Ok(texture)
+}
+
+/// a texture copy to the cpu that's in flight
+///
+/// the texture needs COPY_SRC usage, the data has tightly packed rows
+pub struct TextureReadback {
+    buffer: wgpu::Buffer,
+    unpadded_row: u32,
+    padded_row: u32,
+    mapped: Arc<AtomicBool>,
+}
+
+impl TextureReadback {
+    pub fn start(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) -> Self {
+        let texel_size = texture.format().block_copy_size(None).unwrap();
+        let unpadded_row = texture.width() * texel_size;
+        let padded_row = unpadded_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("texture readback buffer"),
+            size: (padded_row * texture.height()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(texture.height()),
+                },
+            },
+            texture.size(),
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let mapped = Arc::new(AtomicBool::new(false));
+        let mapped_clone = mapped.clone();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                result.unwrap();
+                mapped_clone.store(true, Ordering::Release);
+            });
+
+        TextureReadback {
+            buffer,
+            unpadded_row,
+            padded_row,
+            mapped,
+        }
+    }
+
+    /// returns the data once the copy is done, doesn't block
+    pub fn try_finish(&self) -> Option<Vec<u8>> {
+        if !self.mapped.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let data = self
+            .buffer
+            .slice(..)
+            .get_mapped_range()
+            .chunks(self.padded_row as usize)
+            .flat_map(|row| &row[..self.unpadded_row as usize])
+            .copied()
+            .collect();
+        self.buffer.unmap();
+
+        Some(data)
+    }
+}
+
+/// copies a whole texture to the cpu, blocking until the copy is done
+///
+/// the texture needs COPY_SRC usage, the returned rows are tightly packed
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Vec<u8> {
+    let readback = TextureReadback::start(device, queue, texture);
+    device.poll(wgpu::Maintain::Wait);
+    readback.try_finish().unwrap()
+}
+
+/// encodes Rgba8Unorm texture data as png
+pub fn encode_png(size: (u32, u32), data: Vec<u8>) -> Result<Vec<u8>, String> {
+    let mut png = std::io::Cursor::new(vec![]);
+    image::RgbaImage::from_raw(size.0, size.1, data)
+        .unwrap()
+        .write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|e| format!("Error encoding image: {}", e))?;
+    Ok(png.into_inner())
+}
+
+/// reads back an Rgba8Unorm texture and encodes it as png
+#[cfg(not(target_arch = "wasm32"))]
+pub fn encode_texture_png(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<Vec<u8>, String> {
+    let data = read_texture(device, queue, texture);
+    encode_png((texture.width(), texture.height()), data)
+}
+
+/// reads back an Rgba8Unorm texture and writes it to an image file as is
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    filename: &str,
+) -> Result<(), String> {
+    let format = image::ImageFormat::from_path(filename)
+        .map_err(|e| format!("Error saving image: {}", e))?;
+    let data = read_texture(device, queue, texture);
+    let image = image::RgbaImage::from_raw(texture.width(), texture.height(), data).unwrap();
+    match format {
+        // no alpha channel to write into
+        image::ImageFormat::Jpeg => image::DynamicImage::ImageRgba8(image)
+            .to_rgb8()
+            .save(filename),
+        _ => image.save(filename),
+    }
+    .map_err(|e| format!("Error saving image: {}", e))
+}
+
+/// encodes Rgba16Float render output data as exr, keeping the full float values
+#[cfg(not(target_arch = "wasm32"))]
+pub fn encode_exr(size: (u32, u32), data: Vec<u8>) -> Result<Vec<u8>, String> {
+    let values = data
+        .chunks_exact(2)
+        .map(|c| half::f16::from_le_bytes([c[0], c[1]]).to_f32())
+        .collect::<Vec<f32>>();
+
+    let mut exr = std::io::Cursor::new(vec![]);
+    image::Rgba32FImage::from_raw(size.0, size.1, values)
+        .unwrap()
+        .write_to(&mut exr, image::ImageFormat::OpenExr)
+        .map_err(|e| format!("Error encoding image: {}", e))?;
+    Ok(exr.into_inner())
+}
+
+/// reads back an Rgba16Float render output and writes it to an image file
+///
+/// the format is picked from the extension, exr keeps the full float values,
+/// other formats get the display's exposure and tonemapping so they look like the window
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_output(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    display: &crate::display::Display,
+    filename: &str,
+) -> Result<(), String> {
+    let format = image::ImageFormat::from_path(filename)
+        .map_err(|e| format!("Error saving image: {}", e))?;
+    if format == image::ImageFormat::OpenExr {
+        let data = read_texture(device, queue, texture);
+        let bytes = encode_exr((texture.width(), texture.height()), data)?;
+        return std::fs::write(filename, bytes).map_err(|e| format!("Error saving image: {}", e));
+    }
+
+    let image = display.render_image(device, queue, texture);
+    save_texture(device, queue, &image, filename)
+}
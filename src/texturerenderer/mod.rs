@@ -59,6 +59,20 @@ impl TextureRenderer {
         queue: &wgpu::Queue,
         in_texture: &wgpu::Texture,
         out_texture: &wgpu::Texture,
+    ) {
+        self.render_to_rect(device, queue, in_texture, out_texture, None, None);
+    }
+
+    /// same as render, but only draws to the [x, y, width, height] part of out_texture
+    /// and clears the rest
+    pub fn render_to_rect(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        in_texture: &wgpu::Texture,
+        out_texture: &wgpu::Texture,
+        rect: Option<[f32; 4]>,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
     ) {
         self.screenpass
             .render(&screenpass::ScreenPassRenderDescriptor {
@@ -75,6 +89,8 @@ impl TextureRenderer {
                     ops: wgpu::Operations::default(),
                     resolve_target: None,
                 })],
+                viewport: rect,
+                timestamp_writes,
             });
     }
 }
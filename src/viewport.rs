@@ -0,0 +1,96 @@
+/// maps between window pixels and scene pixels
+///
+/// at zoom 1 the scene is scaled to fit the window, keeping its aspect ratio,
+/// zoom and pan are applied on top of that
+#[derive(Clone, Copy)]
+pub struct Viewport {
+    pub scene_size: (u32, u32),
+    pub window_size: (u32, u32),
+
+    pub zoom: f32,
+    /// offset from the centered position, in window pixels
+    pub pan: (f32, f32),
+}
+
+impl Viewport {
+    const MIN_ZOOM: f32 = 0.1;
+    const MAX_ZOOM: f32 = 256.;
+
+    pub fn new(scene_size: (u32, u32), window_size: (u32, u32)) -> Self {
+        Viewport {
+            scene_size,
+            window_size,
+
+            zoom: 1.,
+            pan: (0., 0.),
+        }
+    }
+
+    /// window pixels per scene pixel
+    pub fn scale(&self) -> f32 {
+        self.zoom
+            * f32::min(
+                self.window_size.0 as f32 / self.scene_size.0 as f32,
+                self.window_size.1 as f32 / self.scene_size.1 as f32,
+            )
+    }
+
+    /// part of the window the scene is drawn to, as [x, y, width, height]
+    ///
+    /// can reach outside of the window when zoomed in
+    pub fn scene_rect(&self) -> [f32; 4] {
+        let scale = self.scale();
+        let size = (
+            self.scene_size.0 as f32 * scale,
+            self.scene_size.1 as f32 * scale,
+        );
+        [
+            (self.window_size.0 as f32 - size.0) / 2. + self.pan.0,
+            (self.window_size.1 as f32 - size.1) / 2. + self.pan.1,
+            size.0,
+            size.1,
+        ]
+    }
+
+    pub fn window_to_scene(&self, pos: (f32, f32)) -> (f32, f32) {
+        let rect = self.scene_rect();
+        let scale = self.scale();
+        ((pos.0 - rect[0]) / scale, (pos.1 - rect[1]) / scale)
+    }
+
+    pub fn scene_to_window(&self, pos: (f32, f32)) -> (f32, f32) {
+        let rect = self.scene_rect();
+        let scale = self.scale();
+        (rect[0] + pos.0 * scale, rect[1] + pos.1 * scale)
+    }
+
+    pub fn contains_scene_pos(&self, pos: (f32, f32)) -> bool {
+        pos.0 >= 0.
+            && pos.1 >= 0.
+            && pos.0 < self.scene_size.0 as f32
+            && pos.1 < self.scene_size.1 as f32
+    }
+
+    /// zooms by `factor`, keeping the scene point under `window_pos` in place
+    pub fn zoom_at(&mut self, window_pos: (f32, f32), factor: f32) {
+        let scene_pos = self.window_to_scene(window_pos);
+        self.zoom = (self.zoom * factor).clamp(Viewport::MIN_ZOOM, Viewport::MAX_ZOOM);
+
+        self.pan = (0., 0.);
+        let centered = self.scene_rect();
+        let scale = self.scale();
+        self.pan = (
+            window_pos.0 - scene_pos.0 * scale - centered[0],
+            window_pos.1 - scene_pos.1 * scale - centered[1],
+        );
+    }
+
+    pub fn pan_by(&mut self, delta: (f32, f32)) {
+        self.pan = (self.pan.0 + delta.0, self.pan.1 + delta.1);
+    }
+
+    pub fn reset_view(&mut self) {
+        self.zoom = 1.;
+        self.pan = (0., 0.);
+    }
+}
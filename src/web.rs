@@ -0,0 +1,114 @@
+// browser replacements for the things the native build does with files
+
+use crate::textureio::TextureReadback;
+use egui_wgpu::wgpu;
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+
+/// lets the user pick a file with an `<input type="file">`
+///
+/// reading the file is async, so the contents show up in `take` a few frames later
+#[derive(Default)]
+pub struct FilePicker {
+    result: Rc<RefCell<Option<Vec<u8>>>>,
+}
+
+impl FilePicker {
+    /// `accept` is the input's accept attribute, like ".png,.jpg"
+    pub fn open(&self, accept: &str) {
+        let document = web_sys::window().unwrap().document().unwrap();
+        let input = document
+            .create_element("input")
+            .unwrap()
+            .dyn_into::<web_sys::HtmlInputElement>()
+            .unwrap();
+        input.set_type("file");
+        input.set_accept(accept);
+
+        let result = self.result.clone();
+        let input_clone = input.clone();
+        let on_change = Closure::once(move || {
+            let Some(file) = input_clone.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            wasm_bindgen_futures::spawn_local(async move {
+                match wasm_bindgen_futures::JsFuture::from(file.array_buffer()).await {
+                    Ok(buffer) => {
+                        *result.borrow_mut() = Some(js_sys::Uint8Array::new(&buffer).to_vec())
+                    }
+                    Err(e) => log::error!("Error reading file: {:?}", e),
+                }
+            });
+        });
+        input.set_onchange(Some(on_change.as_ref().unchecked_ref()));
+        on_change.forget();
+
+        input.click();
+    }
+
+    pub fn take(&self) -> Option<Vec<u8>> {
+        self.result.borrow_mut().take()
+    }
+}
+
+/// makes the browser save `bytes` as a file
+pub fn download(filename: &str, bytes: &[u8]) {
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+    let blob = web_sys::Blob::new_with_u8_array_sequence(&parts).unwrap();
+    let url = web_sys::Url::create_object_url_with_blob(&blob).unwrap();
+
+    let document = web_sys::window().unwrap().document().unwrap();
+    let anchor = document
+        .create_element("a")
+        .unwrap()
+        .dyn_into::<web_sys::HtmlAnchorElement>()
+        .unwrap();
+    anchor.set_href(&url);
+    anchor.set_download(filename);
+    anchor.click();
+
+    web_sys::Url::revoke_object_url(&url).unwrap();
+}
+
+/// turns texture data into a file name and file contents
+type EncodeFile = Box<dyn FnOnce(Vec<u8>) -> Result<(String, Vec<u8>), String>>;
+
+thread_local! {
+    static PENDING_DOWNLOADS: RefCell<Vec<(TextureReadback, EncodeFile)>> = const { RefCell::new(vec![]) };
+}
+
+/// reads back a texture and downloads the file `encode` makes from it
+///
+/// readbacks can't block in the browser, they finish in `poll_downloads`
+pub fn download_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    encode: impl FnOnce(Vec<u8>) -> Result<(String, Vec<u8>), String> + 'static,
+) {
+    let readback = TextureReadback::start(device, queue, texture);
+    PENDING_DOWNLOADS.with_borrow_mut(|pending| pending.push((readback, Box::new(encode))));
+}
+
+/// finishes the downloads whose readbacks are done, called once per frame
+pub fn poll_downloads() {
+    let finished = PENDING_DOWNLOADS.with_borrow_mut(|pending| {
+        let mut finished = vec![];
+        let mut i = 0;
+        while i < pending.len() {
+            match pending[i].0.try_finish() {
+                Some(data) => finished.push((pending.remove(i).1, data)),
+                None => i += 1,
+            }
+        }
+        finished
+    });
+
+    for (encode, data) in finished {
+        match encode(data) {
+            Ok((filename, bytes)) => download(&filename, &bytes),
+            Err(e) => log::error!("{}", e),
+        }
+    }
+}